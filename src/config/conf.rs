@@ -1,8 +1,20 @@
 use ::std::path::Path;
+use ::std::path::PathBuf;
 
+use crate::config::typ::RenameStrategy;
+use crate::config::typ::Scheme;
+use crate::config::typ::ScratchDir;
+use crate::config::typ::SparseMode;
+use crate::config::typ::TimestampPolicy;
 use crate::config::typ::Verbosity;
+use crate::util::ShredResult;
 
 #[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
 pub struct ShredConfig<P: AsRef<Path>> {
     pub files: Vec<P>,
     pub confirmation_prompt: bool,
@@ -11,6 +23,27 @@ pub struct ShredConfig<P: AsRef<Path>> {
     pub overwrite_count: u32,
     pub rename_count: u32,
     pub progress_bar: bool,
+    pub scheme: Scheme,
+    pub punch_holes: bool,
+    pub trim_filesystem: bool,
+    pub sparse: SparseMode,
+    pub wipe_dirents: bool,
+    pub rename_strategy: RenameStrategy,
+    /// Seed for deterministic rename names, only meant for reproducible tests. Names are random if `None`.
+    pub rename_seed: Option<u64>,
+    /// Persist the directory after every rename instead of once after renaming, which is slower.
+    pub sync_each_rename: bool,
+    /// Directory on the same filesystem to move the file into before it is removed.
+    pub scratch_dir: ScratchDir,
+    /// Restore the access and modification times of the directories containing the files afterwards.
+    pub restore_dir_times: bool,
+    pub timestamps: TimestampPolicy,
+    /// Shred protected files: make read-only files and directories writable for the owner, and clear
+    /// immutable and append-only flags (needs root). Files of other users are refused unless root.
+    pub force: bool,
+    /// Set the secure deletion flag (`chattr +s`) before removing. Most filesystems, including
+    /// ext4, ignore it, so it is only set when asked for.
+    pub secure_deletion_flag: bool,
 }
 
 impl<P: AsRef<Path>> ShredConfig<P> {
@@ -29,6 +62,19 @@ impl<P: AsRef<Path>> ShredConfig<P> {
             overwrite_count,
             rename_count,
             progress_bar: false,
+            scheme: Scheme::Fixed,
+            punch_holes: false,
+            trim_filesystem: false,
+            sparse: SparseMode::Densify,
+            wipe_dirents: false,
+            rename_strategy: RenameStrategy::Scrambled,
+            rename_seed: None,
+            sync_each_rename: false,
+            scratch_dir: ScratchDir::InPlace,
+            restore_dir_times: false,
+            timestamps: TimestampPolicy::Zero,
+            force: false,
+            secure_deletion_flag: false,
         }
     }
 
@@ -48,6 +94,495 @@ impl<P: AsRef<Path>> ShredConfig<P> {
             overwrite_count,
             rename_count,
             progress_bar: !verbosity.quiet(),
+            scheme: Scheme::Fixed,
+            punch_holes: false,
+            trim_filesystem: false,
+            sparse: SparseMode::Densify,
+            wipe_dirents: false,
+            rename_strategy: RenameStrategy::Scrambled,
+            rename_seed: None,
+            sync_each_rename: false,
+            scratch_dir: ScratchDir::InPlace,
+            restore_dir_times: false,
+            timestamps: TimestampPolicy::Zero,
+            force: false,
+            secure_deletion_flag: false,
         }
     }
 }
+
+impl<P: AsRef<Path>> ShredConfig<P> {
+    /// Start from the defaults (10 passes and renames, no prompt), and change options by name.
+    pub fn builder() -> ShredConfigBuilder<P> {
+        ShredConfigBuilder {
+            config: ShredConfig::default(),
+        }
+    }
+
+    /// The same options without any files, to keep in a [Shredder](crate::Shredder).
+    pub(crate) fn options(&self) -> ShredConfig<PathBuf> {
+        ShredConfig {
+            files: vec![],
+            confirmation_prompt: self.confirmation_prompt,
+            verbosity: self.verbosity,
+            keep_files: self.keep_files,
+            overwrite_count: self.overwrite_count,
+            rename_count: self.rename_count,
+            progress_bar: self.progress_bar,
+            scheme: self.scheme,
+            punch_holes: self.punch_holes,
+            trim_filesystem: self.trim_filesystem,
+            sparse: self.sparse,
+            wipe_dirents: self.wipe_dirents,
+            rename_strategy: self.rename_strategy,
+            rename_seed: self.rename_seed,
+            sync_each_rename: self.sync_each_rename,
+            scratch_dir: self.scratch_dir.clone(),
+            restore_dir_times: self.restore_dir_times,
+            timestamps: self.timestamps,
+            force: self.force,
+            secure_deletion_flag: self.secure_deletion_flag,
+        }
+    }
+
+    /// Check that the options are valid, which `shred` also does before starting.
+    pub fn validate(&self) -> ShredResult<()> {
+        if self.overwrite_count == 0 {
+            return Err("overwrite-count is 0, but must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl<P: AsRef<Path>> Default for ShredConfig<P> {
+    fn default() -> Self {
+        ShredConfig::non_interactive(vec![], Verbosity::Normal, false, 10, 10)
+    }
+}
+
+/// Builds a [ShredConfig] with named options, and validates it.
+#[derive(Debug)]
+pub struct ShredConfigBuilder<P: AsRef<Path>> {
+    config: ShredConfig<P>,
+}
+
+impl<P: AsRef<Path>> ShredConfigBuilder<P> {
+    pub fn files(mut self, files: impl IntoIterator<Item = P>) -> Self {
+        self.config.files.extend(files);
+        self
+    }
+
+    pub fn file(mut self, file: P) -> Self {
+        self.config.files.push(file);
+        self
+    }
+
+    pub fn confirmation_prompt(mut self, confirmation_prompt: bool) -> Self {
+        self.config.confirmation_prompt = confirmation_prompt;
+        self
+    }
+
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.config.verbosity = verbosity;
+        self
+    }
+
+    pub fn keep_files(mut self, keep_files: bool) -> Self {
+        self.config.keep_files = keep_files;
+        self
+    }
+
+    pub fn overwrite_count(mut self, overwrite_count: u32) -> Self {
+        self.config.overwrite_count = overwrite_count;
+        self
+    }
+
+    pub fn rename_count(mut self, rename_count: u32) -> Self {
+        self.config.rename_count = rename_count;
+        self
+    }
+
+    pub fn progress_bar(mut self, progress_bar: bool) -> Self {
+        self.config.progress_bar = progress_bar;
+        self
+    }
+
+    pub fn scheme(mut self, scheme: Scheme) -> Self {
+        self.config.scheme = scheme;
+        self
+    }
+
+    pub fn punch_holes(mut self, punch_holes: bool) -> Self {
+        self.config.punch_holes = punch_holes;
+        self
+    }
+
+    pub fn trim_filesystem(mut self, trim_filesystem: bool) -> Self {
+        self.config.trim_filesystem = trim_filesystem;
+        self
+    }
+
+    pub fn sparse(mut self, sparse: SparseMode) -> Self {
+        self.config.sparse = sparse;
+        self
+    }
+
+    pub fn wipe_dirents(mut self, wipe_dirents: bool) -> Self {
+        self.config.wipe_dirents = wipe_dirents;
+        self
+    }
+
+    pub fn rename_strategy(mut self, rename_strategy: RenameStrategy) -> Self {
+        self.config.rename_strategy = rename_strategy;
+        self
+    }
+
+    pub fn rename_seed(mut self, rename_seed: u64) -> Self {
+        self.config.rename_seed = Some(rename_seed);
+        self
+    }
+
+    pub fn sync_each_rename(mut self, sync_each_rename: bool) -> Self {
+        self.config.sync_each_rename = sync_each_rename;
+        self
+    }
+
+    pub fn scratch_dir(mut self, scratch_dir: ScratchDir) -> Self {
+        self.config.scratch_dir = scratch_dir;
+        self
+    }
+
+    pub fn restore_dir_times(mut self, restore_dir_times: bool) -> Self {
+        self.config.restore_dir_times = restore_dir_times;
+        self
+    }
+
+    pub fn timestamps(mut self, timestamps: TimestampPolicy) -> Self {
+        self.config.timestamps = timestamps;
+        self
+    }
+
+    pub fn force(mut self, force: bool) -> Self {
+        self.config.force = force;
+        self
+    }
+
+    pub fn secure_deletion_flag(mut self, secure_deletion_flag: bool) -> Self {
+        self.config.secure_deletion_flag = secure_deletion_flag;
+        self
+    }
+
+    /// Validate and return the config.
+    pub fn build(self) -> ShredResult<ShredConfig<P>> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+/// Options for overwriting and sanitizing a whole block device.
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct DeviceConfig {
+    pub device: PathBuf,
+    pub confirmation_prompt: bool,
+    pub verbosity: Verbosity,
+    pub overwrite_count: u32,
+    pub sanitize: bool,
+    /// Byte offset at which to start, must be a multiple of 512.
+    pub offset: u64,
+    /// Number of bytes to erase, or everything after `offset` if `None`. Must be a multiple of 512.
+    pub length: Option<u64>,
+    pub progress_bar: bool,
+}
+
+impl DeviceConfig {
+    pub fn non_interactive(
+        device: PathBuf,
+        verbosity: Verbosity,
+        overwrite_count: u32,
+        sanitize: bool,
+    ) -> Self {
+        DeviceConfig {
+            device,
+            confirmation_prompt: false,
+            verbosity,
+            overwrite_count,
+            sanitize,
+            offset: 0,
+            length: None,
+            progress_bar: false,
+        }
+    }
+
+    pub fn interactive(
+        device: PathBuf,
+        verbosity: Verbosity,
+        overwrite_count: u32,
+        sanitize: bool,
+    ) -> Self {
+        DeviceConfig {
+            device,
+            confirmation_prompt: true,
+            verbosity,
+            overwrite_count,
+            sanitize,
+            offset: 0,
+            length: None,
+            progress_bar: !verbosity.quiet(),
+        }
+    }
+
+    /// Check that the options are valid, which `shred_device` also does before starting.
+    pub fn validate(&self) -> ShredResult<()> {
+        if self.overwrite_count == 0 && !self.sanitize {
+            return Err(
+                "overwrite-count is 0 and sanitize is not set, so nothing would be erased"
+                    .to_owned(),
+            );
+        }
+        if !self.offset.is_multiple_of(512) {
+            return Err(format!("offset {} is not a multiple of 512", self.offset));
+        }
+        if let Some(length) = self.length {
+            if !length.is_multiple_of(512) {
+                return Err(format!("length {} is not a multiple of 512", length));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        DeviceConfig::non_interactive(PathBuf::new(), Verbosity::Normal, 1, false)
+    }
+}
+
+/// Options for wiping the free space of the filesystem containing a directory.
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct FreeSpaceConfig {
+    pub dir: PathBuf,
+    pub confirmation_prompt: bool,
+    pub verbosity: Verbosity,
+    pub overwrite_count: u32,
+    /// Number of bytes to leave free, so that other programs keep working.
+    pub reserve: u64,
+    /// Maximum number of bytes to write, as a safety limit.
+    pub limit: Option<u64>,
+    pub progress_bar: bool,
+}
+
+impl FreeSpaceConfig {
+    pub fn non_interactive(dir: PathBuf, verbosity: Verbosity, overwrite_count: u32) -> Self {
+        FreeSpaceConfig {
+            dir,
+            confirmation_prompt: false,
+            verbosity,
+            overwrite_count,
+            reserve: 100 * 1024 * 1024,
+            limit: None,
+            progress_bar: false,
+        }
+    }
+
+    pub fn interactive(
+        dir: PathBuf,
+        confirmation_prompt: bool,
+        verbosity: Verbosity,
+        overwrite_count: u32,
+    ) -> Self {
+        FreeSpaceConfig {
+            dir,
+            confirmation_prompt,
+            verbosity,
+            overwrite_count,
+            reserve: 100 * 1024 * 1024,
+            limit: None,
+            progress_bar: !verbosity.quiet(),
+        }
+    }
+
+    /// Check that the options are valid, which `shred_free_space` also does before starting.
+    pub fn validate(&self) -> ShredResult<()> {
+        if self.overwrite_count == 0 {
+            return Err("overwrite-count is 0, but must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Default for FreeSpaceConfig {
+    fn default() -> Self {
+        FreeSpaceConfig::non_interactive(PathBuf::new(), Verbosity::Normal, 1)
+    }
+}
+
+/// Options for shrinking a file while overwriting the part that is cut off.
+#[derive(Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct TruncateConfig {
+    pub file: PathBuf,
+    /// Length in bytes to keep.
+    pub length: u64,
+    pub confirmation_prompt: bool,
+    pub verbosity: Verbosity,
+    pub overwrite_count: u32,
+}
+
+impl TruncateConfig {
+    pub fn non_interactive(
+        file: PathBuf,
+        length: u64,
+        verbosity: Verbosity,
+        overwrite_count: u32,
+    ) -> Self {
+        TruncateConfig {
+            file,
+            length,
+            confirmation_prompt: false,
+            verbosity,
+            overwrite_count,
+        }
+    }
+
+    pub fn interactive(
+        file: PathBuf,
+        length: u64,
+        confirmation_prompt: bool,
+        verbosity: Verbosity,
+        overwrite_count: u32,
+    ) -> Self {
+        TruncateConfig {
+            file,
+            length,
+            confirmation_prompt,
+            verbosity,
+            overwrite_count,
+        }
+    }
+
+    /// Check that the options are valid, which `shred_truncate_with` also does before starting.
+    pub fn validate(&self) -> ShredResult<()> {
+        if self.overwrite_count == 0 {
+            return Err("overwrite-count is 0, but must be at least 1".to_owned());
+        }
+        Ok(())
+    }
+}
+
+impl Default for TruncateConfig {
+    fn default() -> Self {
+        TruncateConfig::non_interactive(PathBuf::new(), 0, Verbosity::Normal, 10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_sets_options() {
+        let config = ShredConfig::builder()
+            .file("secret.txt")
+            .verbosity(Verbosity::Quiet)
+            .overwrite_count(3)
+            .rename_strategy(RenameStrategy::Shrinking)
+            .timestamps(TimestampPolicy::Random)
+            .build()
+            .unwrap();
+        assert_eq!(config.files, vec!["secret.txt"]);
+        assert_eq!(config.verbosity, Verbosity::Quiet);
+        assert_eq!(config.overwrite_count, 3);
+        assert_eq!(config.rename_count, 10);
+        assert_eq!(config.rename_strategy, RenameStrategy::Shrinking);
+        assert_eq!(config.timestamps, TimestampPolicy::Random);
+        assert!(!config.confirmation_prompt);
+    }
+
+    #[test]
+    fn builder_validates() {
+        let res = ShredConfig::<PathBuf>::builder().overwrite_count(0).build();
+        assert!(res.unwrap_err().contains("at least 1"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_json() {
+        let config: ShredConfig<PathBuf> = serde_json::from_str(
+            r#"{"files": ["/tmp/secret.txt"], "overwrite_count": 2, "sparse": "data-only", "scratch_dir": "filesystem-root"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.files, vec![PathBuf::from("/tmp/secret.txt")]);
+        assert_eq!(config.overwrite_count, 2);
+        assert_eq!(config.rename_count, 10);
+        assert_eq!(config.sparse, SparseMode::DataOnly);
+        assert_eq!(config.scratch_dir, ScratchDir::FilesystemRoot);
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(json.contains(r#""scheme":"fixed""#));
+    }
+
+    #[test]
+    fn device_validates() {
+        let mut config =
+            DeviceConfig::non_interactive(PathBuf::from("/dev/loop0"), Verbosity::Quiet, 0, false);
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("nothing would be erased"));
+        config.sanitize = true;
+        config.validate().unwrap();
+        config.offset = 1000;
+        assert!(config.validate().unwrap_err().contains("offset 1000"));
+        config.offset = 1024;
+        config.length = Some(100);
+        assert!(config.validate().unwrap_err().contains("length 100"));
+    }
+
+    #[test]
+    fn free_space_validates() {
+        let config = FreeSpaceConfig::non_interactive(PathBuf::from("/mnt"), Verbosity::Quiet, 0);
+        assert!(config.validate().unwrap_err().contains("at least 1"));
+    }
+
+    #[test]
+    fn truncate_validates() {
+        let config =
+            TruncateConfig::non_interactive(PathBuf::from("app.log"), 0, Verbosity::Quiet, 0);
+        assert!(config.validate().unwrap_err().contains("at least 1"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_device_json() {
+        let config: DeviceConfig =
+            serde_json::from_str(r#"{"device": "/dev/loop0", "sanitize": true}"#).unwrap();
+        assert_eq!(config.device, PathBuf::from("/dev/loop0"));
+        assert!(config.sanitize);
+        assert_eq!(config.overwrite_count, 1);
+        assert_eq!(config.length, None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_truncate_json() {
+        let config: TruncateConfig =
+            serde_json::from_str(r#"{"file": "app.log", "length": 4096}"#).unwrap();
+        assert_eq!(config.file, PathBuf::from("app.log"));
+        assert_eq!(config.length, 4096);
+        assert_eq!(config.overwrite_count, 10);
+        assert!(!config.confirmation_prompt);
+    }
+}
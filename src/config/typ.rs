@@ -1,4 +1,15 @@
+use ::std::fmt;
+use ::std::path::PathBuf;
+use ::std::str::FromStr;
+
+use crate::util::escape::escape_path;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
 pub enum Verbosity {
     Quiet,
     #[default]
@@ -15,3 +26,210 @@ impl Verbosity {
         Verbosity::Quiet == self
     }
 }
+
+/// How the number of overwrite passes is decided.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum Scheme {
+    /// Always use the configured number of passes.
+    #[default]
+    Fixed,
+    /// Choose passes per file based on the kind of storage it is on.
+    Auto,
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "fixed" => Ok(Scheme::Fixed),
+            "auto" => Ok(Scheme::Auto),
+            _ => Err(format!(
+                "unknown scheme '{}', expected 'fixed' or 'auto'",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scheme::Fixed => "fixed",
+            Scheme::Auto => "auto",
+        })
+    }
+}
+
+/// How to overwrite sparse files, which have unallocated holes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum SparseMode {
+    /// Overwrite the whole length, which allocates space for all holes.
+    #[default]
+    Densify,
+    /// Only overwrite the regions that contain data, skipping the holes.
+    DataOnly,
+}
+
+impl FromStr for SparseMode {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "densify" => Ok(SparseMode::Densify),
+            "data-only" => Ok(SparseMode::DataOnly),
+            _ => Err(format!(
+                "unknown sparse mode '{}', expected 'densify' or 'data-only'",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SparseMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SparseMode::Densify => "densify",
+            SparseMode::DataOnly => "data-only",
+        })
+    }
+}
+
+/// How files are renamed before they are removed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum RenameStrategy {
+    /// Rename `rename_count` times to unrelated names.
+    #[default]
+    Scrambled,
+    /// Rename to names of the original length, then shorter and shorter (like GNU shred).
+    /// The number of renames is the length of the original name.
+    Shrinking,
+}
+
+impl FromStr for RenameStrategy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "scrambled" => Ok(RenameStrategy::Scrambled),
+            "shrinking" => Ok(RenameStrategy::Shrinking),
+            _ => Err(format!(
+                "unknown rename strategy '{}', expected 'scrambled' or 'shrinking'",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for RenameStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RenameStrategy::Scrambled => "scrambled",
+            RenameStrategy::Shrinking => "shrinking",
+        })
+    }
+}
+
+/// Where the file is moved for the final renames and removal.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum ScratchDir {
+    /// Rename and remove the file in its own directory.
+    #[default]
+    InPlace,
+    /// Use a hidden `.shred-tmp` directory at the root of the file's filesystem, which is
+    /// created if needed and kept for later runs.
+    FilesystemRoot,
+    /// Use this directory, which must be on the same filesystem as the file.
+    Dir(PathBuf),
+}
+
+impl FromStr for ScratchDir {
+    type Err = String;
+
+    /// Anything other than 'in-place' and 'filesystem-root' is taken as a directory.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "" => Err("scratch directory cannot be empty".to_owned()),
+            "in-place" => Ok(ScratchDir::InPlace),
+            "filesystem-root" => Ok(ScratchDir::FilesystemRoot),
+            dir => Ok(ScratchDir::Dir(PathBuf::from(dir))),
+        }
+    }
+}
+
+impl fmt::Display for ScratchDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScratchDir::InPlace => f.write_str("in-place"),
+            ScratchDir::FilesystemRoot => f.write_str("filesystem-root"),
+            ScratchDir::Dir(dir) => f.write_str(&escape_path(dir)),
+        }
+    }
+}
+
+/// What to set the access and modification times of shredded files to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum TimestampPolicy {
+    /// Set to the epoch (1970-01-01), which makes it obvious that the times were removed.
+    #[default]
+    Zero,
+    /// Set to random times within the last year.
+    Random,
+    /// Copy the times from another file in the same directory.
+    Sibling,
+    /// Leave the times as they are after overwriting.
+    Untouched,
+}
+
+impl FromStr for TimestampPolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.to_lowercase().as_str() {
+            "zero" => Ok(TimestampPolicy::Zero),
+            "random" => Ok(TimestampPolicy::Random),
+            "sibling" => Ok(TimestampPolicy::Sibling),
+            "untouched" => Ok(TimestampPolicy::Untouched),
+            _ => Err(format!(
+                "unknown timestamp policy '{}', expected 'zero', 'random', 'sibling' or 'untouched'",
+                text
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TimestampPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimestampPolicy::Zero => "zero",
+            TimestampPolicy::Random => "random",
+            TimestampPolicy::Sibling => "sibling",
+            TimestampPolicy::Untouched => "untouched",
+        })
+    }
+}
@@ -0,0 +1,299 @@
+use ::std::fmt;
+use ::std::fs::File;
+use ::std::path::Path;
+
+use ::indicatif::ProgressBar;
+
+use crate::config::conf::DeviceConfig;
+use crate::erase::discard::DiscardOutcome;
+use crate::erase::overwrite::{overwrite_pass, pass_patterns, OverwriteBuffer};
+use crate::erase::report::FileReport;
+use crate::inspect::device::DeviceInfo;
+use crate::util::ShredResult;
+
+/// Kernel block-layer operations that erase a range of a device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizeMethod {
+    SecureDiscard,
+    Discard,
+    ZeroOut,
+}
+
+impl SanitizeMethod {
+    /// Methods in the order they are attempted; the first one that works is used.
+    pub const FALLBACK_ORDER: [SanitizeMethod; 3] = [
+        SanitizeMethod::SecureDiscard,
+        SanitizeMethod::Discard,
+        SanitizeMethod::ZeroOut,
+    ];
+}
+
+impl fmt::Display for SanitizeMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SanitizeMethod::SecureDiscard => "BLKSECDISCARD",
+            SanitizeMethod::Discard => "BLKDISCARD",
+            SanitizeMethod::ZeroOut => "BLKZEROOUT",
+        })
+    }
+}
+
+/// Check that the range is aligned to sectors and inside the device, and return (start, length).
+pub fn device_range(size: u64, offset: u64, length: Option<u64>) -> ShredResult<(u64, u64)> {
+    if !offset.is_multiple_of(512) {
+        return Err(format!("offset {} is not a multiple of 512", offset));
+    }
+    if offset > size {
+        return Err(format!(
+            "offset {} is beyond the end of the device ({} bytes)",
+            offset, size
+        ));
+    }
+    let length = length.unwrap_or(size - offset);
+    if !length.is_multiple_of(512) {
+        return Err(format!("length {} is not a multiple of 512", length));
+    }
+    if length > size - offset {
+        return Err(format!(
+            "range of {} bytes at offset {} is beyond the end of the device ({} bytes)",
+            length, offset, size
+        ));
+    }
+    Ok((offset, length))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::fs::File;
+    use ::std::fs::OpenOptions;
+    use ::std::io;
+    use ::std::os::unix::fs::OpenOptionsExt;
+    use ::std::os::unix::io::AsRawFd;
+    use ::std::path::Path;
+
+    use crate::erase::device::SanitizeMethod;
+    use crate::erase::discard::DiscardOutcome;
+    use crate::util::errors::add_err;
+    use crate::util::escape::escape_path;
+    use crate::util::ShredResult;
+
+    /// `_IO(0x12, 119)`
+    const BLKDISCARD: u32 = 0x1277;
+    /// `_IO(0x12, 125)`
+    const BLKSECDISCARD: u32 = 0x127d;
+    /// `_IO(0x12, 127)`
+    const BLKZEROOUT: u32 = 0x127f;
+
+    pub fn open_device(path: &Path, verbose: bool) -> ShredResult<File> {
+        // For block devices, O_EXCL makes the kernel refuse if the device is mounted or otherwise in use.
+        match OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_EXCL)
+            .open(path)
+        {
+            Ok(file) => Ok(file),
+            Err(err) if err.raw_os_error() == Some(libc::EBUSY) => Err(format!(
+                "refusing to shred device '{}' because it is in use",
+                escape_path(path)
+            )),
+            Err(err) => Err(add_err(
+                format!(
+                    "could not open device '{}' in write mode",
+                    escape_path(path)
+                ),
+                verbose,
+                err,
+            )),
+        }
+    }
+
+    pub fn sanitize_range(
+        file: &File,
+        method: SanitizeMethod,
+        start: u64,
+        length: u64,
+        verbose: bool,
+    ) -> ShredResult<DiscardOutcome> {
+        let request = match method {
+            SanitizeMethod::SecureDiscard => BLKSECDISCARD,
+            SanitizeMethod::Discard => BLKDISCARD,
+            SanitizeMethod::ZeroOut => BLKZEROOUT,
+        };
+        let mut range = [start, length];
+        let res =
+            unsafe { libc::ioctl(file.as_raw_fd(), request as libc::Ioctl, range.as_mut_ptr()) };
+        if res == 0 {
+            return Ok(DiscardOutcome::Done);
+        }
+        let err = io::Error::last_os_error();
+        match DiscardOutcome::from_error(&err) {
+            Some(outcome) => Ok(outcome),
+            // Some drivers report missing support as an invalid argument.
+            None if err.raw_os_error() == Some(libc::EINVAL) => Ok(DiscardOutcome::Unsupported),
+            None => Err(add_err(
+                format!("failed to sanitize device using {}", method),
+                verbose,
+                err,
+            )),
+        }
+    }
+}
+
+/// Open a block device for writing, failing if the kernel reports it is in use.
+#[cfg(target_os = "linux")]
+pub fn open_device(path: &Path, verbose: bool) -> ShredResult<File> {
+    linux::open_device(path, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn open_device(path: &Path, _verbose: bool) -> ShredResult<File> {
+    Err(format!(
+        "cannot open device '{}' because device mode is only supported on Linux",
+        escape_path(path)
+    ))
+}
+
+/// Erase a range of the device with one block-layer operation.
+#[cfg(target_os = "linux")]
+pub fn sanitize_range(
+    file: &File,
+    method: SanitizeMethod,
+    start: u64,
+    length: u64,
+    verbose: bool,
+) -> ShredResult<DiscardOutcome> {
+    linux::sanitize_range(file, method, start, length, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn sanitize_range(
+    _file: &File,
+    _method: SanitizeMethod,
+    _start: u64,
+    _length: u64,
+    _verbose: bool,
+) -> ShredResult<DiscardOutcome> {
+    Ok(DiscardOutcome::Unsupported)
+}
+
+/// Try the sanitize methods in order until one succeeds, and return the outcome of each attempt.
+pub fn sanitize_with_fallback(
+    file: &File,
+    start: u64,
+    length: u64,
+    verbose: bool,
+) -> ShredResult<Vec<(SanitizeMethod, DiscardOutcome)>> {
+    let mut attempts = vec![];
+    for method in SanitizeMethod::FALLBACK_ORDER {
+        let outcome = sanitize_range(file, method, start, length, verbose)?;
+        attempts.push((method, outcome));
+        if outcome == DiscardOutcome::Done {
+            break;
+        }
+    }
+    Ok(attempts)
+}
+
+/// Overwrite and optionally sanitize a device that was already inspected and confirmed.
+pub fn erase_device(
+    info: &DeviceInfo,
+    config: &DeviceConfig,
+    progress: Option<&ProgressBar>,
+) -> ShredResult<FileReport> {
+    let verbose = config.verbosity.debug();
+    let (start, length) = device_range(info.size, config.offset, config.length)?;
+    let mut file = open_device(&info.path, verbose)?;
+    let mut report = FileReport::new(&info.path);
+
+    let mut buffer = OverwriteBuffer::new();
+    for pattern in pass_patterns(config.overwrite_count) {
+        overwrite_pass(&mut file, start, length, pattern, &mut buffer, verbose)?;
+        if let Some(pb) = progress {
+            pb.inc(1);
+        }
+    }
+    report.note(format!(
+        "overwritten {} bytes at offset {} with {} pass{}",
+        length,
+        start,
+        config.overwrite_count,
+        if config.overwrite_count == 1 {
+            ""
+        } else {
+            "es"
+        }
+    ));
+
+    if config.sanitize {
+        let attempts = sanitize_with_fallback(&file, start, length, verbose)?;
+        for (method, outcome) in &attempts {
+            report.note(format!("{}: {}", method, outcome));
+        }
+        if attempts.last().map(|(_, outcome)| *outcome) != Some(DiscardOutcome::Done) {
+            report.note("WARNING: none of the sanitize operations succeeded");
+        }
+        if let Some(pb) = progress {
+            pb.inc(1);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs;
+    use ::std::path::PathBuf;
+
+    use ::tempfile::NamedTempFile;
+
+    use crate::config::typ::Verbosity;
+
+    use super::*;
+
+    #[test]
+    fn range_checks() {
+        assert_eq!(device_range(4096, 0, None), Ok((0, 4096)));
+        assert_eq!(device_range(4096, 1024, None), Ok((1024, 3072)));
+        assert_eq!(device_range(4096, 512, Some(1024)), Ok((512, 1024)));
+        assert!(device_range(4096, 100, None).is_err());
+        assert!(device_range(4096, 0, Some(100)).is_err());
+        assert!(device_range(4096, 1024, Some(4096)).is_err());
+        assert!(device_range(4096, 8192, None).is_err());
+    }
+
+    #[test]
+    fn erase_range_of_image() {
+        // Regular files do not support the block ioctls, which exercises the fallbacks.
+        let image = NamedTempFile::new().unwrap();
+        fs::write(image.path(), vec![b'x'; 4096]).unwrap();
+        let info = DeviceInfo {
+            path: image.path().to_owned(),
+            name: "test".to_owned(),
+            model: "unknown".to_owned(),
+            size: 4096,
+        };
+        let mut config =
+            DeviceConfig::non_interactive(image.path().to_owned(), Verbosity::Debug, 2, true);
+        config.offset = 1024;
+        config.length = Some(2048);
+        let report = erase_device(&info, &config, None).unwrap();
+        let data = fs::read(image.path()).unwrap();
+        assert_eq!(data.len(), 4096);
+        assert!(data[..1024].iter().all(|b| *b == b'x'));
+        assert!(data[3072..].iter().all(|b| *b == b'x'));
+        assert_ne!(&data[1024..3072], vec![b'x'; 2048].as_slice());
+        assert!(report.notes.iter().any(|n| n.contains("BLKZEROOUT")));
+    }
+
+    /// Needs root and a scratch loop device, e.g. from `losetup -f --show image.bin`,
+    /// passed as `FILE_SHRED_TEST_DEVICE`. Run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn erase_loop_device() {
+        let device = PathBuf::from(std::env::var("FILE_SHRED_TEST_DEVICE").unwrap());
+        let info = crate::inspect::device::inspect_device(&device, true).unwrap();
+        let config = DeviceConfig::non_interactive(device, Verbosity::Debug, 1, true);
+        let report = erase_device(&info, &config, None).unwrap();
+        assert!(report.notes.iter().any(|n| n.ends_with(": done")));
+    }
+}
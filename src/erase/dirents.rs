@@ -0,0 +1,136 @@
+use ::std::fs;
+use ::std::fs::OpenOptions;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use ::rand::distr::Alphanumeric;
+use ::rand::Rng;
+
+use crate::util::errors::add_err;
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Roughly the space taken by one directory entry with a maximum-length name.
+const ENTRY_SIZE: u64 = 256;
+const MIN_ENTRIES: u64 = 16;
+const MAX_ENTRIES: u64 = 100_000;
+
+/// Maximum length of a file name in the directory.
+#[cfg(unix)]
+fn name_max(dir: &Path) -> usize {
+    use ::std::ffi::CString;
+    use ::std::os::unix::ffi::OsStrExt;
+
+    let Ok(c_path) = CString::new(dir.as_os_str().as_bytes()) else {
+        return 255;
+    };
+    let res = unsafe { libc::pathconf(c_path.as_ptr(), libc::_PC_NAME_MAX) };
+    if res <= 0 {
+        return 255;
+    }
+    (res as usize).min(255)
+}
+
+#[cfg(not(unix))]
+fn name_max(_dir: &Path) -> usize {
+    255
+}
+
+/// Number of entries needed to overwrite the free slots of a directory of this size.
+pub fn entry_count(dir_size: u64) -> u64 {
+    (dir_size / ENTRY_SIZE).clamp(MIN_ENTRIES, MAX_ENTRIES)
+}
+
+/// Create and then remove a burst of files with maximum-length random names, so that directory
+/// entries that still contain names of removed files are overwritten. Returns the number of entries.
+pub fn wipe_directory_entries(dir: &Path, verbose: bool) -> ShredResult<u64> {
+    let dir_size = match fs::metadata(dir) {
+        Ok(meta) if meta.is_dir() => meta.len(),
+        Ok(_) => return Err(format!("path '{}' is not a directory", escape_path(dir))),
+        Err(err) => {
+            return Err(add_err(
+                format!("could not read directory '{}'", escape_path(dir)),
+                verbose,
+                err,
+            ))
+        }
+    };
+    let count = entry_count(dir_size);
+    let name_len = name_max(dir);
+    let mut rng = rand::rng();
+    let mut created: Vec<PathBuf> = Vec::with_capacity(count as usize);
+
+    // All entries must exist at the same time, otherwise the same slot is reused.
+    let mut failure = None;
+    for _ in 0..count {
+        let name: String = (&mut rng)
+            .sample_iter(&Alphanumeric)
+            .take(name_len)
+            .map(char::from)
+            .collect();
+        let path = dir.join(name);
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => created.push(path),
+            Err(err) => {
+                failure = Some(add_err(
+                    "could not create file to overwrite directory entries",
+                    verbose,
+                    err,
+                ));
+                break;
+            }
+        }
+    }
+    for path in &created {
+        if let Err(err) = fs::remove_file(path) {
+            return Err(add_err(
+                format!(
+                    "could not remove file '{}' used to overwrite directory entries",
+                    escape_path(path)
+                ),
+                verbose,
+                err,
+            ));
+        }
+    }
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(created.len() as u64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn count_scales_with_size() {
+        assert_eq!(entry_count(0), MIN_ENTRIES);
+        assert_eq!(entry_count(4096), 16);
+        assert_eq!(entry_count(40960), 160);
+        assert_eq!(entry_count(u64::MAX), MAX_ENTRIES);
+    }
+
+    #[test]
+    fn wipe_leaves_directory_unchanged() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("keep.me"), b"data").unwrap();
+        let count = wipe_directory_entries(dir.path(), true).unwrap();
+        assert!(count >= MIN_ENTRIES);
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec!["keep.me"]);
+    }
+
+    #[test]
+    fn wipe_requires_directory() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        assert!(wipe_directory_entries(&path, true).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use ::std::fmt;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::util::ShredResult;
+
+/// Whether the storage accepted a request to discard blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscardOutcome {
+    Done,
+    Unsupported,
+    NotPermitted,
+}
+
+impl fmt::Display for DiscardOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DiscardOutcome::Done => "done",
+            DiscardOutcome::Unsupported => "not supported",
+            DiscardOutcome::NotPermitted => "not permitted",
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl DiscardOutcome {
+    /// Interpret errors that mean discarding is not possible, as opposed to failing.
+    pub fn from_error(err: &::std::io::Error) -> Option<Self> {
+        match err.raw_os_error() {
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) | Some(libc::ENOTTY) => {
+                Some(DiscardOutcome::Unsupported)
+            }
+            Some(libc::EPERM) | Some(libc::EACCES) => Some(DiscardOutcome::NotPermitted),
+            _ => None,
+        }
+    }
+}
+
+/// Directory that contains the file, also for relative paths without a directory part.
+pub fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::collections::HashSet;
+    use ::std::fs::File;
+    use ::std::fs::OpenOptions;
+    use ::std::io;
+    use ::std::os::unix::fs::MetadataExt;
+    use ::std::os::unix::io::AsRawFd;
+    use ::std::path::Path;
+    use ::std::path::PathBuf;
+
+    use crate::erase::discard::DiscardOutcome;
+    use crate::util::errors::add_err;
+    use crate::util::escape::escape_path;
+    use crate::util::ShredResult;
+
+    /// `_IOWR('X', 121, struct fstrim_range)`
+    const FITRIM: u32 = 0xc018_5879;
+
+    #[repr(C)]
+    struct FstrimRange {
+        start: u64,
+        len: u64,
+        minlen: u64,
+    }
+
+    pub fn punch_holes(path: &Path, verbose: bool) -> ShredResult<DiscardOutcome> {
+        let file = match OpenOptions::new().write(true).open(path) {
+            Ok(file) => file,
+            Err(err) => {
+                return Err(add_err(
+                    "failed to open file to discard its blocks",
+                    verbose,
+                    err,
+                ))
+            }
+        };
+        let len = match file.metadata() {
+            Ok(meta) => meta.len(),
+            Err(err) => return Err(add_err("could not inspect file", verbose, err)),
+        };
+        if len == 0 {
+            return Ok(DiscardOutcome::Done);
+        }
+        let res = unsafe {
+            libc::fallocate(
+                file.as_raw_fd(),
+                libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE,
+                0,
+                len as libc::off_t,
+            )
+        };
+        if res == 0 {
+            return Ok(DiscardOutcome::Done);
+        }
+        let err = io::Error::last_os_error();
+        match DiscardOutcome::from_error(&err) {
+            Some(outcome) => Ok(outcome),
+            None => Err(add_err("failed to discard file blocks", verbose, err)),
+        }
+    }
+
+    fn trim_filesystem(dir: &Path, verbose: bool) -> ShredResult<DiscardOutcome> {
+        let handle = match File::open(dir) {
+            Ok(handle) => handle,
+            Err(err) => {
+                return Err(add_err(
+                    format!(
+                        "failed to open directory '{}' to trim its filesystem",
+                        escape_path(dir)
+                    ),
+                    verbose,
+                    err,
+                ))
+            }
+        };
+        let mut range = FstrimRange {
+            start: 0,
+            len: u64::MAX,
+            minlen: 0,
+        };
+        let res = unsafe {
+            libc::ioctl(
+                handle.as_raw_fd(),
+                FITRIM as libc::Ioctl,
+                &mut range as *mut FstrimRange,
+            )
+        };
+        if res == 0 {
+            return Ok(DiscardOutcome::Done);
+        }
+        let err = io::Error::last_os_error();
+        match DiscardOutcome::from_error(&err) {
+            Some(outcome) => Ok(outcome),
+            None => Err(add_err("failed to trim filesystem", verbose, err)),
+        }
+    }
+
+    pub fn trim_filesystems(
+        dirs: &[&Path],
+        verbose: bool,
+    ) -> ShredResult<Vec<(PathBuf, DiscardOutcome)>> {
+        let mut seen = HashSet::new();
+        let mut outcomes = vec![];
+        for dir in dirs {
+            let dev = match dir.metadata() {
+                Ok(meta) => meta.dev(),
+                Err(err) => {
+                    return Err(add_err(
+                        format!("could not inspect directory '{}'", escape_path(dir)),
+                        verbose,
+                        err,
+                    ))
+                }
+            };
+            if seen.insert(dev) {
+                outcomes.push((dir.to_path_buf(), trim_filesystem(dir, verbose)?));
+            }
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Deallocate the blocks of the file while keeping its size, so that SSDs and thin-provisioned
+/// storage learn that they are free. The file reads as zeros afterwards.
+#[cfg(target_os = "linux")]
+pub fn punch_holes(path: &Path, verbose: bool) -> ShredResult<DiscardOutcome> {
+    linux::punch_holes(path, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn punch_holes(_path: &Path, _verbose: bool) -> ShredResult<DiscardOutcome> {
+    Ok(DiscardOutcome::Unsupported)
+}
+
+/// Ask each filesystem containing one of the directories to discard all its unused blocks,
+/// like `fstrim`. This usually requires root.
+#[cfg(target_os = "linux")]
+pub fn trim_filesystems(
+    dirs: &[&Path],
+    verbose: bool,
+) -> ShredResult<Vec<(PathBuf, DiscardOutcome)>> {
+    linux::trim_filesystems(dirs, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn trim_filesystems(
+    dirs: &[&Path],
+    _verbose: bool,
+) -> ShredResult<Vec<(PathBuf, DiscardOutcome)>> {
+    Ok(dirs
+        .iter()
+        .map(|dir| (dir.to_path_buf(), DiscardOutcome::Unsupported))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs;
+
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn punch_keeps_size() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("punch.me");
+        fs::write(&path, vec![b'x'; 20_000]).unwrap();
+        let outcome = punch_holes(&path, true).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), 20_000);
+        if outcome == DiscardOutcome::Done {
+            assert!(data.iter().all(|byte| *byte == 0));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn trim_missing_dir() {
+        let res = trim_filesystems(&[Path::new("/this/dir/does/not/exist")], false);
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn parent_of_relative() {
+        assert_eq!(parent_dir(Path::new("file.txt")), Path::new("."));
+        assert_eq!(parent_dir(Path::new("dir/file.txt")), Path::new("dir"));
+    }
+}
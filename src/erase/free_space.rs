@@ -0,0 +1,251 @@
+use ::std::fmt;
+use ::std::fs;
+use ::std::fs::File;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use ::indicatif::ProgressBar;
+use ::rand::distr::Alphanumeric;
+use ::rand::Rng;
+
+use crate::config::conf::FreeSpaceConfig;
+use crate::erase::overwrite::{pass_patterns, OverwriteBuffer};
+use crate::erase::remove::{remove_file, truncate_file};
+use crate::erase::report::FileReport;
+use crate::inspect::storage::available_space;
+use crate::util::errors::{add_err, wrap_io};
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Size of each filler file, so that no single file becomes huge.
+const FILLER_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Why filling the free space stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillStop {
+    DiskFull,
+    Reserve,
+    Limit,
+}
+
+impl fmt::Display for FillStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FillStop::DiskFull => "the disk was full",
+            FillStop::Reserve => "only the reserve was left",
+            FillStop::Limit => "the safety limit was reached",
+        })
+    }
+}
+
+fn create_work_dir(dir: &Path, verbose: bool) -> ShredResult<PathBuf> {
+    let suffix: String = rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+    let work_dir = dir.join(format!(".shred-free-space-{}", suffix));
+    match fs::create_dir(&work_dir) {
+        Ok(()) => Ok(work_dir),
+        Err(err) => Err(add_err(
+            format!(
+                "could not create directory for filler files in '{}'",
+                escape_path(dir)
+            ),
+            verbose,
+            err,
+        )),
+    }
+}
+
+fn fill_space(
+    work_dir: &Path,
+    config: &FreeSpaceConfig,
+    target: u64,
+    fillers: &mut Vec<PathBuf>,
+    progress: Option<&ProgressBar>,
+) -> ShredResult<(u64, bool)> {
+    let verbose = config.verbosity.debug();
+    let patterns = pass_patterns(config.overwrite_count);
+    let Some((first, rest)) = patterns.split_first() else {
+        return Err("overwrite count must be at least 1".to_owned());
+    };
+    let mut buffer = OverwriteBuffer::new();
+    let mut total = 0;
+    while total < target {
+        // Other programs may be using space too, so keep checking the reserve.
+        let free = available_space(work_dir, verbose)?.saturating_sub(config.reserve);
+        let size = FILLER_SIZE.min(target - total).min(free);
+        if size == 0 {
+            return Ok((total, false));
+        }
+        let path = work_dir.join(format!("filler{:06}", fillers.len()));
+        let mut file = wrap_io(|| "could not create filler file", File::create(&path))?;
+        fillers.push(path);
+        let (written, full) = buffer.fill(&mut file, 0, size, *first, verbose)?;
+        for pattern in rest {
+            // Persist the previous pass, then write exactly the filled length again; should the
+            // disk still be full (e.g. copy-on-write filesystems), that ends the pass like it
+            // ended filling.
+            wrap_io(|| "could not persist filler file", file.sync_data())?;
+            buffer.fill(&mut file, 0, written, *pattern, verbose)?;
+        }
+        wrap_io(|| "could not persist filler file", file.sync_all())?;
+        total += written;
+        if let Some(pb) = progress {
+            pb.inc(written / 1024);
+        }
+        if full {
+            return Ok((total, true));
+        }
+    }
+    Ok((total, false))
+}
+
+/// Overwrite the free space of the filesystem containing the directory, by filling it with
+/// filler files (except for a reserve) and removing those afterwards.
+pub fn wipe_free_space(
+    config: &FreeSpaceConfig,
+    progress: Option<&ProgressBar>,
+) -> ShredResult<FileReport> {
+    let verbose = config.verbosity.debug();
+    let mut report = FileReport::new(&config.dir);
+    let mut target = available_space(&config.dir, verbose)?.saturating_sub(config.reserve);
+    let mut stop = FillStop::Reserve;
+    if let Some(limit) = config.limit {
+        if limit < target {
+            target = limit;
+            stop = FillStop::Limit;
+        }
+    }
+    if let Some(pb) = progress {
+        pb.set_length(target / 1024);
+    }
+
+    let work_dir = create_work_dir(&config.dir, verbose)?;
+    let mut fillers = vec![];
+    let filled = fill_space(&work_dir, config, target, &mut fillers, progress);
+
+    // Always clean up, also if filling failed, and try every filler before reporting problems.
+    let mut errors = vec![];
+    if let Err(err) = &filled {
+        errors.push(err.clone());
+    }
+    for filler in &fillers {
+        if let Err(err) = truncate_file(filler, verbose) {
+            errors.push(err);
+        }
+        if let Err(err) = remove_file(filler, verbose) {
+            errors.push(err);
+        }
+    }
+    if let Err(err) = fs::remove_dir(&work_dir) {
+        errors.push(add_err(
+            format!(
+                "could not remove directory of filler files '{}'",
+                escape_path(&work_dir)
+            ),
+            verbose,
+            err,
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    let (total, full) = filled?;
+    if full {
+        stop = FillStop::DiskFull;
+    } else if total < target {
+        stop = FillStop::Reserve;
+    }
+    report.note(format!(
+        "filled {} bytes of free space using {} filler file{}, stopped because {}",
+        total,
+        fillers.len(),
+        if fillers.len() == 1 { "" } else { "s" },
+        stop
+    ));
+    report.note("removed filler files");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::io;
+    use ::std::io::Cursor;
+    use ::std::io::Seek;
+    use ::std::io::SeekFrom;
+    use ::std::io::Write;
+
+    use ::tempfile::tempdir;
+
+    use crate::config::typ::Verbosity;
+
+    use super::*;
+
+    /// Writer that reports a full disk after a number of bytes.
+    struct SmallDisk {
+        data: Vec<u8>,
+        capacity: usize,
+    }
+
+    impl Write for SmallDisk {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let space = self.capacity - self.data.len();
+            if space == 0 {
+                return Err(io::Error::from(io::ErrorKind::StorageFull));
+            }
+            let count = buf.len().min(space);
+            self.data.extend_from_slice(&buf[..count]);
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Only appends, so the position is always the end.
+    impl Seek for SmallDisk {
+        fn seek(&mut self, _pos: SeekFrom) -> io::Result<u64> {
+            Ok(self.data.len() as u64)
+        }
+    }
+
+    #[test]
+    fn fill_until_size() {
+        let mut file = Cursor::new(vec![]);
+        let (written, full) = OverwriteBuffer::new()
+            .fill(&mut file, 0, 100_000, Some(b'z'), true)
+            .unwrap();
+        assert_eq!(written, 100_000);
+        assert!(!full);
+        assert!(file.get_ref().iter().all(|b| *b == b'z'));
+    }
+
+    #[test]
+    fn fill_until_disk_full() {
+        let mut disk = SmallDisk {
+            data: vec![],
+            capacity: 70_000,
+        };
+        let (written, full) = OverwriteBuffer::new()
+            .fill(&mut disk, 0, 1_000_000, None, true)
+            .unwrap();
+        assert_eq!(written, 70_000);
+        assert!(full);
+    }
+
+    #[test]
+    fn wipe_with_limit() {
+        let dir = tempdir().unwrap();
+        let mut config =
+            FreeSpaceConfig::non_interactive(dir.path().to_owned(), Verbosity::Debug, 2);
+        config.reserve = 0;
+        config.limit = Some(3 * 1024 * 1024);
+        let report = wipe_free_space(&config, None).unwrap();
+        assert!(report.notes[0].contains("safety limit"));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
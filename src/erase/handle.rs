@@ -0,0 +1,282 @@
+#[cfg(unix)]
+use ::std::ffi::OsStr;
+#[cfg(unix)]
+use ::std::fs;
+use ::std::fs::File;
+#[cfg(unix)]
+use ::std::io;
+#[cfg(unix)]
+use ::std::os::fd::BorrowedFd;
+use ::std::path::Path;
+
+use crate::config::conf::ShredConfig;
+#[cfg(unix)]
+use crate::config::typ::RenameStrategy;
+#[cfg(unix)]
+use crate::erase::discard::parent_dir;
+use crate::erase::overwrite::{overwrite_file, OverwriteBuffer, OverwriteSummary};
+use crate::erase::remove::truncate_handle;
+#[cfg(unix)]
+use crate::erase::rename::{repeatedly_rename_file, shrinking_rename_file};
+#[cfg(unix)]
+use crate::util::errors::add_err;
+use crate::util::errors::wrap_io;
+#[cfg(unix)]
+use crate::util::escape::escape_os;
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Overwrite an open file, and truncate it unless `keep_files` is set. Works on the descriptor
+/// only, so it cannot be confused by the path being replaced. The file must be open for writing,
+/// and not in append mode.
+pub fn shred_handle<P: AsRef<Path>>(
+    file: &mut File,
+    config: &ShredConfig<P>,
+) -> ShredResult<OverwriteSummary> {
+    config.validate()?;
+    let verbose = config.verbosity.debug();
+    refuse_append(file, verbose)?;
+    let written = overwrite_file(
+        file,
+        config.overwrite_count,
+        config.sparse,
+        &mut OverwriteBuffer::new(),
+        verbose,
+    )?;
+    if !config.keep_files {
+        truncate_handle(file, 0, verbose)?;
+        wrap_io(|| "could not persist truncated file", file.sync_all())?;
+    }
+    Ok(written)
+}
+
+/// Rename and remove a file that was shredded through `file`, but only while `path` still
+/// refers to that file, so that nothing put at the path in the meantime is removed.
+/// Where that cannot be checked, nothing is removed and an error is returned.
+#[cfg(unix)]
+pub fn remove_shredded<P: AsRef<Path>>(
+    path: &Path,
+    file: File,
+    config: &ShredConfig<P>,
+) -> ShredResult<()> {
+    let verbose = config.verbosity.debug();
+    if !is_same_file(path, &file) {
+        return Err(format!(
+            "'{}' is no longer the shredded file, so it was not removed (the data was overwritten)",
+            escape_path(path)
+        ));
+    }
+    let renamed = match config.rename_strategy {
+        RenameStrategy::Scrambled => repeatedly_rename_file(
+            path,
+            config.rename_count,
+            config.rename_seed,
+            config.sync_each_rename,
+            verbose,
+        )?,
+        RenameStrategy::Shrinking => shrinking_rename_file(path, config.sync_each_rename, verbose)?,
+    };
+    remove_open_file(&renamed, file, verbose)
+}
+
+#[cfg(not(unix))]
+pub fn remove_shredded<P: AsRef<Path>>(
+    path: &Path,
+    _file: File,
+    _config: &ShredConfig<P>,
+) -> ShredResult<()> {
+    Err(format!(
+        "cannot check that '{}' is still the shredded file on this platform, so it was not removed (the data was overwritten)",
+        escape_path(path)
+    ))
+}
+
+#[cfg(unix)]
+fn is_same_file(path: &Path, file: &File) -> bool {
+    use ::std::os::unix::fs::MetadataExt;
+    match (fs::symlink_metadata(path), file.metadata()) {
+        (Ok(at_path), Ok(open)) => at_path.dev() == open.dev() && at_path.ino() == open.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(unix)]
+fn remove_open_file(path: &Path, file: File, verbose: bool) -> ShredResult<()> {
+    use ::std::os::fd::AsFd;
+    let dir = parent_dir(path);
+    let dir_handle = wrap_io(
+        || format!("could not open directory '{}'", escape_path(dir)),
+        File::open(dir),
+    )?;
+    let Some(name) = path.file_name() else {
+        return Err(format!("'{}' has no file name", escape_path(path)));
+    };
+    unlink_at(dir_handle.as_fd(), name, &file, verbose)
+}
+
+/// Writes to a file open in append mode all go to the end, so they would not overwrite anything.
+#[cfg(unix)]
+fn refuse_append(file: &File, verbose: bool) -> ShredResult<()> {
+    use ::std::os::fd::AsRawFd;
+
+    let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+    if flags == -1 {
+        return Err(add_err(
+            "could not read the status flags of the open file",
+            verbose,
+            io::Error::last_os_error(),
+        ));
+    }
+    if flags & libc::O_APPEND != 0 {
+        return Err(
+            "file is open in append mode, so it cannot be overwritten (open it without append)"
+                .to_owned(),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn refuse_append(_file: &File, _verbose: bool) -> ShredResult<()> {
+    Ok(())
+}
+
+/// Remove the name from the directory, if it still refers to the open file, and persist
+/// the directory. Does not follow symlinks.
+#[cfg(unix)]
+pub fn unlink_at(dir: BorrowedFd, name: &OsStr, file: &File, verbose: bool) -> ShredResult<()> {
+    use ::std::ffi::CString;
+    use ::std::mem::MaybeUninit;
+    use ::std::os::fd::AsRawFd;
+    use ::std::os::unix::ffi::OsStrExt;
+    use ::std::os::unix::fs::MetadataExt;
+
+    if name.is_empty() || name.as_bytes().contains(&b'/') {
+        return Err(format!(
+            "'{}' is not a plain file name, so it cannot be removed from the directory",
+            escape_os(name)
+        ));
+    }
+    let Ok(c_name) = CString::new(name.as_bytes()) else {
+        return Err(format!(
+            "file name '{}' contains a nul byte",
+            escape_os(name)
+        ));
+    };
+
+    // Only remove the name if it still refers to the shredded file.
+    let meta = match file.metadata() {
+        Ok(meta) => meta,
+        Err(err) => return Err(add_err("could not inspect open file", verbose, err)),
+    };
+    let mut stat = MaybeUninit::<libc::stat>::uninit();
+    let res = unsafe {
+        libc::fstatat(
+            dir.as_raw_fd(),
+            c_name.as_ptr(),
+            stat.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    if res != 0 {
+        return Err(add_err(
+            format!("could not find '{}' in the directory", escape_os(name)),
+            verbose,
+            io::Error::last_os_error(),
+        ));
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    if stat.st_dev as u64 != meta.dev() || stat.st_ino as u64 != meta.ino() {
+        return Err(format!(
+            "'{}' in the directory is no longer the open file, so it was not removed",
+            escape_os(name)
+        ));
+    }
+
+    if unsafe { libc::unlinkat(dir.as_raw_fd(), c_name.as_ptr(), 0) } != 0 {
+        return Err(add_err(
+            format!("could not remove '{}' from the directory", escape_os(name)),
+            verbose,
+            io::Error::last_os_error(),
+        ));
+    }
+    let res = dir
+        .try_clone_to_owned()
+        .and_then(|dir| File::from(dir).sync_all());
+    if let Err(err) = res {
+        return Err(add_err("could not persist directory", verbose, err));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs;
+    use ::std::fs::OpenOptions;
+    use ::std::io::Write;
+    use ::std::path::PathBuf;
+
+    use ::tempfile::tempdir;
+
+    use crate::config::typ::Verbosity;
+
+    use super::*;
+
+    fn config(keep_files: bool) -> ShredConfig<PathBuf> {
+        ShredConfig::non_interactive(vec![], Verbosity::Debug, keep_files, 2, 0)
+    }
+
+    #[test]
+    fn overwrite_and_truncate_handle() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"secret").unwrap();
+        let written = shred_handle(&mut file, &config(false)).unwrap();
+        assert_eq!(written.data_bytes, 6);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn keep_handle() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"secret").unwrap();
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        shred_handle(&mut file, &config(true)).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), 6);
+        assert_ne!(&data, b"secret");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuse_append_handle() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"secret").unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        let err = shred_handle(&mut file, &config(false)).unwrap_err();
+        assert!(err.contains("append mode"));
+        assert_eq!(fs::read(&path).unwrap(), b"secret");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unlink_only_same_file() {
+        use ::std::ffi::OsStr;
+        use ::std::os::fd::AsFd;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        let file = File::create(&path).unwrap();
+        fs::write(dir.path().join("other.txt"), b"other").unwrap();
+        let dir_handle = File::open(dir.path()).unwrap();
+        let err = unlink_at(dir_handle.as_fd(), OsStr::new("other.txt"), &file, true).unwrap_err();
+        assert!(err.contains("no longer the open file"));
+        assert!(unlink_at(dir_handle.as_fd(), OsStr::new("../file.txt"), &file, true).is_err());
+        unlink_at(dir_handle.as_fd(), OsStr::new("file.txt"), &file, true).unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -1,18 +1,456 @@
+use ::std::fs;
 use ::std::path::Path;
+use ::std::path::PathBuf;
 
 use ::filetime::{set_file_times, FileTime};
+use ::rand::Rng;
 
+use crate::config::typ::TimestampPolicy;
+use crate::erase::discard::parent_dir;
 use crate::util::errors::add_err;
+use crate::util::escape::escape_path;
 use crate::util::ShredResult;
 
-/// Remove access and modification times by setting to zero timestamp.
-pub fn remove_file_times(path: &Path, verbose: bool) -> ShredResult<()> {
-    match set_file_times(path, FileTime::zero(), FileTime::zero()) {
+/// Access and modification times of a directory, recorded before shredding to restore them later.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirTimes {
+    pub dir: PathBuf,
+    accessed: FileTime,
+    modified: FileTime,
+}
+
+/// Window before now in which random times are chosen.
+const RANDOM_WINDOW_SECONDS: i64 = 365 * 24 * 60 * 60;
+
+/// Random (access, modification) times within the last year, with access after modification.
+fn random_times() -> (FileTime, FileTime) {
+    let now = FileTime::now().unix_seconds();
+    let mut rng = rand::rng();
+    let modified = now - rng.random_range(0..RANDOM_WINDOW_SECONDS);
+    let accessed = rng.random_range(modified..=now);
+    (
+        FileTime::from_unix_time(accessed, rng.random_range(0..1_000_000_000)),
+        FileTime::from_unix_time(modified, rng.random_range(0..1_000_000_000)),
+    )
+}
+
+/// Times of the first other regular file in the same directory, with its name.
+fn sibling_times(path: &Path) -> Option<(FileTime, FileTime, PathBuf)> {
+    let mut siblings: Vec<_> = fs::read_dir(parent_dir(path))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|sibling| sibling.file_name() != path.file_name())
+        .collect();
+    siblings.sort();
+    siblings.into_iter().find_map(|sibling| {
+        let meta = fs::symlink_metadata(&sibling).ok()?;
+        if !meta.is_file() {
+            return None;
+        }
+        Some((
+            FileTime::from_last_access_time(&meta),
+            FileTime::from_last_modification_time(&meta),
+            sibling,
+        ))
+    })
+}
+
+/// Replace the access and modification times according to the policy, so they do not reveal
+/// when the file was shredded. Returns a note for the report.
+pub fn replace_file_times(
+    path: &Path,
+    policy: TimestampPolicy,
+    verbose: bool,
+) -> ShredResult<String> {
+    let (accessed, modified, note) = match policy {
+        TimestampPolicy::Untouched => return Ok("left access and modification times".to_owned()),
+        TimestampPolicy::Zero => (
+            FileTime::zero(),
+            FileTime::zero(),
+            "removed access and modification times".to_owned(),
+        ),
+        TimestampPolicy::Random => {
+            let (accessed, modified) = random_times();
+            (
+                accessed,
+                modified,
+                "set random access and modification times".to_owned(),
+            )
+        }
+        TimestampPolicy::Sibling => match sibling_times(path) {
+            Some((accessed, modified, sibling)) => (
+                accessed,
+                modified,
+                format!(
+                    "copied access and modification times from '{}'",
+                    escape_path(&sibling)
+                ),
+            ),
+            None => {
+                let (accessed, modified) = random_times();
+                (
+                    accessed,
+                    modified,
+                    "set random access and modification times, because there is no other file to copy them from".to_owned(),
+                )
+            }
+        },
+    };
+    match set_file_times(path, accessed, modified) {
+        Ok(()) => Ok(note),
+        Err(err) => Err(add_err(
+            "failed to set file times while shredding",
+            verbose,
+            err,
+        )),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::ffi::CString;
+    use ::std::ffi::OsStr;
+    use ::std::io;
+    use ::std::os::unix::ffi::OsStrExt;
+    use ::std::path::Path;
+
+    use crate::util::escape::escape_os;
+    use crate::util::privilege::is_root;
+
+    /// Extended attributes that hold POSIX ACLs, which are removed as a separate step.
+    const ACL_NAMES: [&[u8]; 2] = [b"system.posix_acl_access", b"system.posix_acl_default"];
+
+    fn c_string(bytes: &[u8]) -> io::Result<CString> {
+        CString::new(bytes).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+    }
+
+    pub fn list_xattrs(path: &Path) -> io::Result<Vec<Vec<u8>>> {
+        let c_path = c_string(path.as_os_str().as_bytes())?;
+        loop {
+            let size = unsafe { libc::llistxattr(c_path.as_ptr(), std::ptr::null_mut(), 0) };
+            if size < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut buffer = vec![0u8; size as usize];
+            let size = unsafe {
+                libc::llistxattr(
+                    c_path.as_ptr(),
+                    buffer.as_mut_ptr() as *mut libc::c_char,
+                    buffer.len(),
+                )
+            };
+            if size < 0 {
+                let err = io::Error::last_os_error();
+                // Attributes were added in between, so try again with a bigger buffer.
+                if err.raw_os_error() == Some(libc::ERANGE) {
+                    continue;
+                }
+                return Err(err);
+            }
+            buffer.truncate(size as usize);
+            return Ok(buffer
+                .split(|b| *b == 0)
+                .filter(|name| !name.is_empty())
+                .map(|name| name.to_vec())
+                .collect());
+        }
+    }
+
+    fn remove_xattr(path: &Path, name: &[u8]) -> io::Result<()> {
+        let c_path = c_string(path.as_os_str().as_bytes())?;
+        let c_name = c_string(name)?;
+        if unsafe { libc::lremovexattr(c_path.as_ptr(), c_name.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn is_unsupported(err: &io::Error) -> bool {
+        err.raw_os_error() == Some(libc::EOPNOTSUPP)
+    }
+
+    pub fn remove_xattrs(path: &Path) -> Vec<String> {
+        let names = match list_xattrs(path) {
+            Ok(names) => names,
+            Err(err) if is_unsupported(&err) => {
+                return vec!["extended attributes: not supported by the filesystem".to_owned()]
+            }
+            Err(err) => return vec![format!("could not list extended attributes: {}", err)],
+        };
+        let mut notes = vec![];
+        let mut removed = 0;
+        for name in names
+            .iter()
+            .filter(|name| !ACL_NAMES.contains(&name.as_slice()))
+        {
+            match remove_xattr(path, name) {
+                Ok(()) => removed += 1,
+                Err(err) => notes.push(format!(
+                    "could not remove extended attribute '{}': {}",
+                    escape_os(OsStr::from_bytes(name)),
+                    err
+                )),
+            }
+        }
+        notes.insert(
+            0,
+            format!(
+                "removed {} extended attribute{}",
+                removed,
+                if removed == 1 { "" } else { "s" }
+            ),
+        );
+        notes
+    }
+
+    pub fn remove_acl(path: &Path) -> String {
+        // Some filesystems report success when removing an ACL that does not exist, so check first.
+        let names = match list_xattrs(path) {
+            Ok(names) => names,
+            Err(err) if is_unsupported(&err) => {
+                return "POSIX ACL: not supported by the filesystem".to_owned()
+            }
+            Err(err) => return format!("could not check POSIX ACL: {}", err),
+        };
+        let mut removed = false;
+        for name in ACL_NAMES {
+            if !names.iter().any(|present| present == name) {
+                continue;
+            }
+            match remove_xattr(path, name) {
+                Ok(()) => removed = true,
+                Err(err) if err.raw_os_error() == Some(libc::ENODATA) => {}
+                Err(err) => return format!("could not remove POSIX ACL: {}", err),
+            }
+        }
+        if removed {
+            "removed POSIX ACL".to_owned()
+        } else {
+            "no POSIX ACL to remove".to_owned()
+        }
+    }
+
+    pub fn remove_owner(path: &Path) -> String {
+        if !is_root() {
+            return "ownership unchanged, only possible as root".to_owned();
+        }
+        let res = c_string(path.as_os_str().as_bytes()).and_then(|c_path| {
+            if unsafe { libc::lchown(c_path.as_ptr(), 0, 0) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+        match res {
+            Ok(()) => "changed owner to 0:0".to_owned(),
+            Err(err) => format!("could not change owner: {}", err),
+        }
+    }
+}
+
+/// Remove extended attributes (like download origin markers), except ACLs. Never fails;
+/// returns a note for the report about each problem.
+#[cfg(target_os = "linux")]
+fn remove_xattrs(path: &Path) -> Vec<String> {
+    linux::remove_xattrs(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn remove_xattrs(_path: &Path) -> Vec<String> {
+    vec!["extended attributes: not supported on this platform".to_owned()]
+}
+
+#[cfg(target_os = "linux")]
+fn remove_acl(path: &Path) -> String {
+    linux::remove_acl(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn remove_acl(_path: &Path) -> String {
+    "POSIX ACL: not supported on this platform".to_owned()
+}
+
+#[cfg(target_os = "linux")]
+fn remove_owner(path: &Path) -> String {
+    linux::remove_owner(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn remove_owner(_path: &Path) -> String {
+    "ownership unchanged, not supported on this platform".to_owned()
+}
+
+#[cfg(unix)]
+fn remove_permissions(path: &Path) -> String {
+    use ::std::os::unix::fs::PermissionsExt;
+    match fs::set_permissions(path, fs::Permissions::from_mode(0o000)) {
+        Ok(()) => "set permissions to 0000".to_owned(),
+        Err(err) => format!("could not remove permissions: {}", err),
+    }
+}
+
+#[cfg(not(unix))]
+fn remove_permissions(path: &Path) -> String {
+    let mut perms = match fs::metadata(path) {
+        Ok(meta) => meta.permissions(),
+        Err(err) => return format!("could not remove permissions: {}", err),
+    };
+    perms.set_readonly(true);
+    match fs::set_permissions(path, perms) {
+        Ok(()) => "made read-only".to_owned(),
+        Err(err) => format!("could not remove permissions: {}", err),
+    }
+}
+
+/// Remove extended attributes, ACLs, ownership (as root) and permissions of the file.
+/// Each step fails softly, so the result is a note for the report about each step.
+/// Afterwards, the file can no longer be opened by its owner, but it can still be renamed and removed.
+pub fn scrub_metadata(path: &Path) -> Vec<String> {
+    let mut notes = remove_xattrs(path);
+    notes.push(remove_acl(path));
+    notes.push(remove_owner(path));
+    notes.push(remove_permissions(path));
+    notes
+}
+
+pub fn record_dir_times(dir: &Path, verbose: bool) -> ShredResult<DirTimes> {
+    match fs::metadata(dir) {
+        Ok(meta) => Ok(DirTimes {
+            dir: dir.to_owned(),
+            accessed: FileTime::from_last_access_time(&meta),
+            modified: FileTime::from_last_modification_time(&meta),
+        }),
+        Err(err) => Err(add_err(
+            format!("could not read times of directory '{}'", escape_path(dir)),
+            verbose,
+            err,
+        )),
+    }
+}
+
+/// Set the access and modification times back to the recorded ones. The change time (ctime)
+/// is updated by this too, and cannot be restored by normal means.
+pub fn restore_dir_times(times: &DirTimes, verbose: bool) -> ShredResult<()> {
+    match set_file_times(&times.dir, times.accessed, times.modified) {
         Ok(()) => Ok(()),
         Err(err) => Err(add_err(
-            "failed to set file permissions while shredding",
+            format!(
+                "could not restore times of directory '{}'",
+                escape_path(&times.dir)
+            ),
             verbose,
             err,
         )),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn scrub_removes_xattrs_and_permissions() {
+        use ::std::ffi::CString;
+        use ::std::os::unix::ffi::OsStrExt;
+        use ::std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("download.zip");
+        fs::write(&path, b"data").unwrap();
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        let url = b"https://example.com/download.zip";
+        let res = unsafe {
+            libc::setxattr(
+                c_path.as_ptr(),
+                c"user.xdg.origin.url".as_ptr(),
+                url.as_ptr() as *const libc::c_void,
+                url.len(),
+                0,
+            )
+        };
+        let notes = scrub_metadata(&path);
+        if res == 0 {
+            assert_eq!(notes[0], "removed 1 extended attribute");
+        }
+        assert!(notes.iter().any(|note| note == "set permissions to 0000"));
+        let meta = fs::metadata(&path).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o7777, 0);
+        if let Ok(names) = linux::list_xattrs(&path) {
+            assert!(names.is_empty());
+        }
+    }
+
+    fn times(path: &Path) -> (FileTime, FileTime) {
+        let meta = fs::metadata(path).unwrap();
+        (
+            FileTime::from_last_access_time(&meta),
+            FileTime::from_last_modification_time(&meta),
+        )
+    }
+
+    #[test]
+    fn zero_times() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        replace_file_times(&path, TimestampPolicy::Zero, true).unwrap();
+        assert_eq!(times(&path), (FileTime::zero(), FileTime::zero()));
+    }
+
+    #[test]
+    fn random_times_in_window() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        replace_file_times(&path, TimestampPolicy::Random, true).unwrap();
+        let (accessed, modified) = times(&path);
+        let now = FileTime::now().unix_seconds();
+        assert!(modified.unix_seconds() > now - RANDOM_WINDOW_SECONDS - 1);
+        assert!(modified <= accessed);
+        assert!(accessed.unix_seconds() <= now);
+    }
+
+    #[test]
+    fn sibling_times_copied() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        let sibling = dir.path().join("other.txt");
+        fs::write(&path, b"data").unwrap();
+        fs::write(&sibling, b"data").unwrap();
+        let old = FileTime::from_unix_time(1_500_000_000, 0);
+        set_file_times(&sibling, old, old).unwrap();
+        let note = replace_file_times(&path, TimestampPolicy::Sibling, true).unwrap();
+        assert!(note.contains("other.txt"));
+        assert_eq!(times(&path), (old, old));
+    }
+
+    #[test]
+    fn untouched_times() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let before = times(&path);
+        replace_file_times(&path, TimestampPolicy::Untouched, true).unwrap();
+        assert_eq!(times(&path), before);
+    }
+
+    #[test]
+    fn restore_times_after_removal() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        set_file_times(dir.path(), old, old).unwrap();
+        let times = record_dir_times(dir.path(), true).unwrap();
+        fs::remove_file(&path).unwrap();
+        let changed = fs::metadata(dir.path()).unwrap();
+        assert_ne!(FileTime::from_last_modification_time(&changed), old);
+        restore_dir_times(&times, true).unwrap();
+        let restored = fs::metadata(dir.path()).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&restored), old);
+        assert_eq!(FileTime::from_last_access_time(&restored), old);
+    }
+}
@@ -1,5 +1,17 @@
+pub mod device;
+pub mod dirents;
+pub mod discard;
+pub mod free_space;
+pub mod handle;
 pub mod metadata;
 pub mod orchestrate;
 pub mod overwrite;
+pub mod permissions;
 pub mod remove;
 pub mod rename;
+pub mod replace;
+pub mod report;
+pub mod scratch;
+pub mod shredder;
+pub mod tail;
+pub mod temp_file;
@@ -1,30 +1,203 @@
 use ::std::path::Path;
+use ::std::path::PathBuf;
 
 use crate::config::conf::ShredConfig;
-use crate::erase::metadata::remove_file_times;
-use crate::erase::overwrite::repeatedly_overwrite;
-use crate::erase::remove::{remove_file, truncate_file};
-use crate::erase::rename::repeatedly_rename_file;
+use crate::config::typ::{RenameStrategy, ScratchDir, SparseMode};
+use crate::erase::discard::{parent_dir, punch_holes};
+use crate::erase::metadata::{replace_file_times, scrub_metadata};
+use crate::erase::overwrite::{repeatedly_overwrite, OverwriteBuffer};
+use crate::erase::permissions::{make_writable, WritableGuard};
+use crate::erase::remove::{remove_file, sync_parent_dir, truncate_file};
+use crate::erase::rename::{repeatedly_rename_file, shrinking_rename_lengths};
+use crate::erase::report::FileReport;
+use crate::erase::scratch::move_to_scratch;
+use crate::inspect::collect::FileInfo;
+use crate::inspect::flags::{add_flag, clear_protection, FlagsError, HelpfulFlag};
+use crate::util::escape::escape_path;
 use crate::util::ShredResult;
 
 /// Shred a file, overwriting it with random data repeatedly, and subsequently deleting.
-pub fn delete_file<P: AsRef<Path>>(path: &Path, config: &ShredConfig<P>) -> ShredResult<()> {
+pub fn delete_file<P: AsRef<Path>>(
+    file: &FileInfo,
+    config: &ShredConfig<P>,
+    buffer: &mut OverwriteBuffer,
+) -> ShredResult<FileReport> {
     let verbose = config.verbosity.debug();
+    let path = file.path;
+    let mut report = FileReport::new(path);
+
+    // Make the file writable and ask the filesystem for help, if possible. The flags and
+    // permissions are restored when the guards are dropped, also when returning early with
+    // an error; the flags last, as they would prevent restoring the permissions.
+    let mut protection = None;
+    if file.flags.is_protected() {
+        match clear_protection(path, file.flags) {
+            Ok(guard) => protection = Some(guard),
+            Err(err) => {
+                return Err(format!(
+                    "could not clear {} flag of '{}': {}",
+                    file.flags,
+                    escape_path(path),
+                    err
+                ))
+            }
+        }
+        report.note(format!("cleared {} flag", file.flags));
+    }
+    let mut file_permissions = WritableGuard::unchanged(path);
+    let mut dir_permissions = WritableGuard::unchanged(parent_dir(path));
+    if config.force {
+        file_permissions = make_writable(path, verbose)?;
+        if file_permissions.changed() {
+            report.note("temporarily added owner write permission");
+        }
+        if !config.keep_files {
+            dir_permissions = make_writable(parent_dir(path), verbose)?;
+            if dir_permissions.changed() {
+                report.note("temporarily added owner write permission to the directory");
+            }
+        }
+    }
+    if config.secure_deletion_flag && !config.keep_files {
+        let flag = HelpfulFlag::SecureDeletion;
+        match add_flag(path, flag) {
+            Ok(()) => report.note(format!("set {} flag, which most filesystems ignore", flag)),
+            Err(FlagsError::Unsupported) => {}
+            Err(err) => report.note(format!("could not set {} flag: {}", flag, err)),
+        }
+    }
 
     // Overwrite the file.
-    repeatedly_overwrite(path, config.overwrite_count, verbose)?;
+    let written = repeatedly_overwrite(
+        path,
+        file.scheme.overwrite_count,
+        config.sparse,
+        buffer,
+        verbose,
+    )?;
+    report.note(format!("overwritten with {}", file.scheme));
+    if config.sparse == SparseMode::DataOnly {
+        report.note(format!(
+            "overwrote {} bytes of data, skipping holes",
+            written.data_bytes
+        ));
+    }
+    if written.beyond_eof_bytes > 0 {
+        report.note(format!(
+            "also overwrote {} bytes preallocated beyond the end of the file",
+            written.beyond_eof_bytes
+        ));
+    }
+
+    // Tell the storage that the blocks are free.
+    if config.punch_holes || file.scheme.discard {
+        let outcome = punch_holes(path, verbose)?;
+        report.note(format!("discard of file blocks: {}", outcome));
+    }
     if config.keep_files {
-        return Ok(());
+        if file_permissions.restore()? {
+            report.note("restored permissions");
+        }
+        report.note(replace_file_times(path, config.timestamps, verbose)?);
+        if let Some(guard) = protection {
+            if let Err(err) = guard.restore() {
+                return Err(format!(
+                    "could not restore {} flag of '{}': {}",
+                    file.flags,
+                    escape_path(path),
+                    err
+                ));
+            }
+            report.note(format!("restored {} flag", file.flags));
+        }
+        return Ok(report);
     }
 
-    // Remove metadata.
-    //TODO @mark: remove permissions (on some platforms?)
-    remove_file_times(path, verbose)?;
+    // Empty the file while it is still writable, then remove metadata.
+    truncate_file(path, verbose)?;
+    for note in scrub_metadata(path) {
+        report.note(note);
+    }
+    // The permissions were just scrubbed, and the file is about to be removed.
+    file_permissions.dismiss();
+    if let Some(guard) = protection {
+        guard.dismiss();
+    }
+    report.note(replace_file_times(path, config.timestamps, verbose)?);
 
     // Rename the file.
-    let renamed_path = repeatedly_rename_file(path, config.rename_count, verbose)?;
+    let renamed_path = if config.scratch_dir == ScratchDir::InPlace {
+        rename_file(path, config, RenameRounds::All, verbose)?
+    } else {
+        // Overwrite the name once, then move out of the original directory, so the remaining
+        // renames and the removal do not happen there.
+        let first_path = rename_file(path, config, RenameRounds::First, verbose)?;
+        let (moved_path, fallback) = move_to_scratch(&first_path, &config.scratch_dir, verbose)?;
+        match fallback {
+            Some(reason) => report.note(format!(
+                "could not use scratch directory, renaming and removing in place: {}",
+                reason
+            )),
+            None if moved_path.parent() != path.parent() => {
+                report.note("moved to scratch directory before the last renames and removal")
+            }
+            None => {}
+        }
+        rename_file(&moved_path, config, RenameRounds::Rest, verbose)?
+    };
 
     // Delete the file
-    truncate_file(&renamed_path, verbose)?;
-    remove_file(&renamed_path, verbose)
+    remove_file(&renamed_path, verbose)?;
+    sync_parent_dir(&renamed_path, verbose)?;
+    report.note("truncated, renamed and removed");
+    if dir_permissions.restore()? {
+        report.note("restored permissions of the directory");
+    }
+    Ok(report)
+}
+
+/// Which of the renames of the strategy to do, so that they can be split around moving the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenameRounds {
+    All,
+    /// One rename (for shrinking: to the name of the same length).
+    First,
+    /// All but the first.
+    Rest,
+}
+
+fn rename_file<P: AsRef<Path>>(
+    path: &Path,
+    config: &ShredConfig<P>,
+    rounds: RenameRounds,
+    verbose: bool,
+) -> ShredResult<PathBuf> {
+    match config.rename_strategy {
+        RenameStrategy::Scrambled => {
+            let count = match rounds {
+                RenameRounds::All => config.rename_count,
+                RenameRounds::First => config.rename_count.min(1),
+                RenameRounds::Rest => config.rename_count.saturating_sub(1),
+            };
+            if count == 0 {
+                return Ok(path.to_owned());
+            }
+            repeatedly_rename_file(
+                path,
+                count,
+                config.rename_seed,
+                config.sync_each_rename,
+                verbose,
+            )
+        }
+        RenameStrategy::Shrinking => {
+            let name_len = path.file_name().map(|name| name.len()).unwrap_or(0);
+            let lengths = match rounds {
+                RenameRounds::All => 1..=name_len,
+                RenameRounds::First => name_len..=name_len,
+                RenameRounds::Rest => 1..=name_len.saturating_sub(1),
+            };
+            shrinking_rename_lengths(path, lengths, config.sync_each_rename, verbose)
+        }
+    }
 }
@@ -1,17 +1,28 @@
 use ::std::fs::File;
+use ::std::fs::Metadata;
 use ::std::fs::OpenOptions;
+use ::std::io;
 use ::std::io::Seek;
 use ::std::io::SeekFrom;
 use ::std::io::Write;
 use ::std::path::Path;
-use ::std::rc::Rc;
 
+use ::rand::rngs::StdRng;
 use ::rand::RngCore;
+use ::rand::SeedableRng;
 
+use crate::config::typ::SparseMode;
+use crate::inspect::extents::allocated_end;
+use crate::inspect::sparse::data_regions;
 use crate::util::errors::add_err;
 use crate::util::errors::wrap_io;
+use crate::util::escape::escape_path;
 use crate::util::ShredResult;
 
+/// Regions are overwritten in whole blocks of this size.
+const BLOCK_SIZE: u64 = 512;
+const BUFFER_SIZE: usize = 64 * 1024;
+
 fn sync(file: &mut File) -> ShredResult<()> {
     wrap_io(
         || "could not persist file while shredding",
@@ -19,50 +30,58 @@ fn sync(file: &mut File) -> ShredResult<()> {
     )
 }
 
-pub fn repeatedly_overwrite(path: &Path, overwrite_count: u32, verbose: bool) -> ShredResult<()> {
+/// Pattern of each overwrite pass: a few fixed bit patterns first, then random data (`None`).
+/// The last pass is always random.
+pub fn pass_patterns(overwrite_count: u32) -> Vec<Option<u8>> {
+    let fixed = [0, 255, 85, 170]; // 00000000, 11111111, 01010101, 10101010
+    let fixed_count = (overwrite_count.saturating_sub(1) as usize).min(fixed.len());
+    let mut patterns: Vec<_> = fixed[..fixed_count].iter().map(|p| Some(*p)).collect();
+    patterns.resize(overwrite_count as usize, None);
+    patterns
+}
+
+/// Do a single overwrite pass over part of the file, and sync afterwards.
+pub fn overwrite_pass(
+    file: &mut File,
+    start: u64,
+    size: u64,
+    pattern: Option<u8>,
+    buffer: &mut OverwriteBuffer,
+    verbose: bool,
+) -> ShredResult<()> {
+    buffer.overwrite(file, start, size, pattern, verbose)?;
+    sync(file)
+}
+
+/// Number of bytes overwritten in each pass, for reporting.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OverwriteSummary {
+    pub data_bytes: u64,
+    /// Space that was preallocated beyond the end of the file.
+    pub beyond_eof_bytes: u64,
+}
+
+/// Overwrite the file a number of times, including any space preallocated beyond its end.
+pub fn repeatedly_overwrite(
+    path: &Path,
+    overwrite_count: u32,
+    sparse: SparseMode,
+    buffer: &mut OverwriteBuffer,
+    verbose: bool,
+) -> ShredResult<OverwriteSummary> {
     match OpenOptions::new()
         .read(false)
         .write(true)
         .append(false)
         .open(path)
     {
-        Ok(mut file) => {
-            let mut count = overwrite_count;
-            let file_meta = wrap_io(|| "could not inspect file", file.metadata())?;
-            assert!(file_meta.is_file());
-            let file_size = file_meta.len();
-            if count > 1 {
-                overwrite_constant(&mut file, file_size, verbose, 0)?; // 00000000
-                sync(&mut file)?;
-                count -= 1;
-            }
-            if count > 1 {
-                overwrite_constant(&mut file, file_size, verbose, 255)?; // 11111111
-                sync(&mut file)?;
-                count -= 1;
-            }
-            if count > 1 {
-                overwrite_constant(&mut file, file_size, verbose, 85)?; // 01010101
-                sync(&mut file)?;
-                count -= 1;
-            }
-            if count > 1 {
-                overwrite_constant(&mut file, file_size, verbose, 170)?; // 10101010
-                sync(&mut file)?;
-                count -= 1;
-            }
-            for _ in 0..count {
-                overwrite_random_data(&mut file, file_size, verbose)?;
-                sync(&mut file)?;
-            }
-            Ok(())
-        }
+        Ok(mut file) => overwrite_file(&mut file, overwrite_count, sparse, buffer, verbose),
         Err(err) => {
             if path.exists() {
                 Err(add_err(
                     format!(
-                        "could not remove file '{}' because it could not be opened in write mode",
-                        path.to_string_lossy()
+                        "could not remove file '{}' because it could not be opened in write mode (use --force for read-only files)",
+                        escape_path(path)
                     ),
                     verbose,
                     err,
@@ -71,7 +90,7 @@ pub fn repeatedly_overwrite(path: &Path, overwrite_count: u32, verbose: bool) ->
                 Err(add_err(
                     format!(
                         "could not remove file '{}' because it does not exist",
-                        path.to_string_lossy()
+                        escape_path(path)
                     ),
                     verbose,
                     err,
@@ -81,65 +100,216 @@ pub fn repeatedly_overwrite(path: &Path, overwrite_count: u32, verbose: bool) ->
     }
 }
 
-pub fn overwrite_constant<F: Write + Seek>(
-    file: &mut F,
-    file_size: u64,
+/// Overwrite an open file a number of times, including any space preallocated beyond its end.
+/// The file must be open for writing.
+pub fn overwrite_file(
+    file: &mut File,
+    overwrite_count: u32,
+    sparse: SparseMode,
+    buffer: &mut OverwriteBuffer,
     verbose: bool,
-    value: u8,
-) -> ShredResult<()> {
-    let data = Rc::new([value; 512]);
-    overwrite_data(file, file_size, verbose, || data.clone())
+) -> ShredResult<OverwriteSummary> {
+    let file_meta = wrap_io(|| "could not inspect file", file.metadata())?;
+    if !file_meta.is_file() {
+        return Err("can only overwrite regular files".to_owned());
+    }
+    let file_size = file_meta.len();
+    let regions = match sparse {
+        SparseMode::Densify => vec![(0, file_size)],
+        SparseMode::DataOnly => data_regions(file, file_size, verbose)?,
+    };
+    let data_bytes = regions.iter().map(|(_, size)| size).sum();
+    let beyond_eof_bytes =
+        overwrite_regions(file, &file_meta, regions, overwrite_count, buffer, verbose)?;
+    Ok(OverwriteSummary {
+        data_bytes,
+        beyond_eof_bytes,
+    })
 }
 
-pub fn overwrite_random_data<F: Write + Seek>(
-    file: &mut F,
-    file_size: u64,
+/// Overwrite the `(start, size)` regions of the file a number of times, together with any space
+/// preallocated beyond its end. Returns the number of bytes that were preallocated.
+pub fn overwrite_regions(
+    file: &mut File,
+    file_meta: &Metadata,
+    mut regions: Vec<(u64, u64)>,
+    overwrite_count: u32,
+    buffer: &mut OverwriteBuffer,
     verbose: bool,
-) -> ShredResult<()> {
-    let mut rng = rand::rng();
-    overwrite_data(file, file_size, verbose, || {
-        let mut data = [0u8; 512];
-        rng.fill_bytes(&mut data);
-        Rc::new(data)
-    })
+) -> ShredResult<u64> {
+    let file_size = file_meta.len();
+    // Blocks preallocated beyond the end can only be written by temporarily extending the file.
+    let beyond_eof_bytes = match allocated_end(file, verbose)? {
+        Some(end) if end > block_aligned_len(file_meta) => end - file_size,
+        _ => 0,
+    };
+    let mut result = Ok(());
+    if beyond_eof_bytes > 0 {
+        result = wrap_io(
+            || "could not extend file to overwrite preallocated space",
+            file.set_len(file_size + beyond_eof_bytes),
+        );
+        regions.push((file_size, beyond_eof_bytes));
+    }
+    if result.is_ok() {
+        result = pass_patterns(overwrite_count)
+            .into_iter()
+            .try_for_each(|pattern| {
+                regions.iter().try_for_each(|(start, size)| {
+                    overwrite_pass(file, *start, *size, pattern, buffer, verbose)
+                })
+            });
+    }
+    // Also when overwriting failed, as the file should not change length.
+    result.and(restore_len(file, file_size, verbose))?;
+    Ok(beyond_eof_bytes)
 }
 
-/// Overwrite the data with garbage.
-/// It is recommended to sync the file after each step.
-fn overwrite_data<F: Write + Seek>(
-    file: &mut F,
-    file_size: u64,
-    verbose: bool,
-    mut value_gen: impl FnMut() -> Rc<[u8; 512]>,
-) -> ShredResult<()> {
-    // Jump to start of file
-    match file.seek(SeekFrom::Start(0)) {
-        Ok(size) => assert_eq!(size, 0),
-        Err(err) => {
+/// Extents cover whole blocks, so the last block is allocated in full even if the file ends
+/// halfway through it. Only allocation beyond that block is preallocated space.
+#[cfg(unix)]
+fn block_aligned_len(meta: &Metadata) -> u64 {
+    use ::std::os::unix::fs::MetadataExt;
+    let block = meta.blksize().max(1);
+    meta.len().div_ceil(block) * block
+}
+
+#[cfg(not(unix))]
+fn block_aligned_len(meta: &Metadata) -> u64 {
+    meta.len()
+}
+
+/// Set the length back after extending the file, or after writing whole blocks past its end.
+fn restore_len(file: &mut File, file_size: u64, verbose: bool) -> ShredResult<()> {
+    let len = wrap_io(|| "could not inspect file", file.metadata())?.len();
+    if len != file_size {
+        if let Err(err) = file.set_len(file_size) {
             return Err(add_err(
-                "could not just to start of file during shredding",
+                "could not restore file length after overwriting",
                 verbose,
                 err,
-            ))
+            ));
         }
+        sync(file)?;
     }
+    Ok(())
+}
 
-    // Overwrite the data in blocks. Might overwrite a bit at the end.
-    let steps = (file_size + 511) / 512;
-    for _ in 0..steps {
-        match file.write(&*value_gen()) {
-            Ok(size) => assert_eq!(size, 512),
+/// Random generator and buffer for overwriting, kept so that they are set up once and reused
+/// for every pass and every file.
+#[derive(Debug)]
+pub struct OverwriteBuffer {
+    rng: StdRng,
+    data: Vec<u8>,
+}
+
+impl OverwriteBuffer {
+    pub fn new() -> Self {
+        OverwriteBuffer {
+            rng: StdRng::from_rng(&mut rand::rng()),
+            data: vec![0; BUFFER_SIZE],
+        }
+    }
+
+    /// Overwrite the data with the pattern, or random data if `None`, starting at `start`.
+    /// It is recommended to sync the file after each step.
+    pub fn overwrite<F: Write + Seek>(
+        &mut self,
+        file: &mut F,
+        start: u64,
+        size: u64,
+        pattern: Option<u8>,
+        verbose: bool,
+    ) -> ShredResult<()> {
+        // Overwrite the data in whole blocks. Might overwrite a bit at the end.
+        let len = size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        match self.write_region(file, start, len, pattern, verbose)? {
+            (_, Ok(())) => Ok(()),
+            (_, Err(err)) => Err(add_err(
+                "could not overwrite file during shredding",
+                verbose,
+                err,
+            )),
+        }
+    }
+
+    /// Like [OverwriteBuffer::overwrite], but writes exactly `size` bytes, and stops early
+    /// without an error when the disk is full. Returns the number of bytes written, and
+    /// whether the disk was full.
+    pub fn fill<F: Write + Seek>(
+        &mut self,
+        file: &mut F,
+        start: u64,
+        size: u64,
+        pattern: Option<u8>,
+        verbose: bool,
+    ) -> ShredResult<(u64, bool)> {
+        match self.write_region(file, start, size, pattern, verbose)? {
+            (written, Ok(())) => Ok((written, false)),
+            (written, Err(err))
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::StorageFull | io::ErrorKind::WriteZero
+                ) =>
+            {
+                Ok((written, true))
+            }
+            (_, Err(err)) => Err(add_err("could not write filler file", verbose, err)),
+        }
+    }
+
+    /// Write `len` bytes at `start`. Returns how much was written, and the error that stopped
+    /// writing, if any.
+    fn write_region<F: Write + Seek>(
+        &mut self,
+        file: &mut F,
+        start: u64,
+        len: u64,
+        pattern: Option<u8>,
+        verbose: bool,
+    ) -> ShredResult<(u64, io::Result<()>)> {
+        // Jump to start of the region
+        match file.seek(SeekFrom::Start(start)) {
+            Ok(pos) => assert_eq!(pos, start),
             Err(err) => {
                 return Err(add_err(
-                    "could not overwrite file during shredding",
+                    "could not just to start of file during shredding",
                     verbose,
                     err,
                 ))
             }
         }
+
+        if let Some(value) = pattern {
+            self.data.fill(value);
+        }
+        let mut written = 0;
+        while written < len {
+            let chunk = (len - written).min(self.data.len() as u64) as usize;
+            if pattern.is_none() {
+                self.rng.fill_bytes(&mut self.data[..chunk]);
+            }
+            let mut offset = 0;
+            while offset < chunk {
+                match file.write(&self.data[offset..chunk]) {
+                    Ok(0) => return Ok((written, Err(io::ErrorKind::WriteZero.into()))),
+                    Ok(count) => {
+                        offset += count;
+                        written += count as u64;
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(err) => return Ok((written, Err(err))),
+                }
+            }
+        }
+        Ok((written, Ok(())))
     }
+}
 
-    Ok(())
+impl Default for OverwriteBuffer {
+    fn default() -> Self {
+        OverwriteBuffer::new()
+    }
 }
 
 #[cfg(test)]
@@ -151,7 +321,9 @@ mod tests {
     #[test]
     fn overwrite_long() {
         let mut mock_file = Cursor::new(vec![0u8; 65_536 + 1]);
-        overwrite_constant(&mut mock_file, 65_536 + 1, false, b'm').unwrap();
+        OverwriteBuffer::new()
+            .overwrite(&mut mock_file, 0, 65_536 + 1, Some(b'm'), false)
+            .unwrap();
         let data = mock_file.get_ref();
         assert!(data.starts_with(b"mmmmmm"));
         assert!(data.ends_with(b"mmmmmm"));
@@ -161,7 +333,9 @@ mod tests {
     #[test]
     fn overwrite_fixed() {
         let mut mock_file = Cursor::new(b"hello world".to_vec());
-        overwrite_constant(&mut mock_file, 11, true, 85).unwrap();
+        OverwriteBuffer::new()
+            .overwrite(&mut mock_file, 0, 11, Some(85), true)
+            .unwrap();
         let data = mock_file.get_ref();
         assert!(!data.starts_with(b"hello world"));
         assert!(data.starts_with(b"UUUUUUUUUUUUUUUUUUUUUUUUUUUUUUUU"));
@@ -172,9 +346,76 @@ mod tests {
     fn overwrite_random() {
         let initial = b"hello world this is an unlikely message that shouldn't happen by chance!";
         let mut mock_file = Cursor::new(initial.to_vec());
-        overwrite_constant(&mut mock_file, 11, true, 85).unwrap();
+        OverwriteBuffer::new()
+            .overwrite(&mut mock_file, 0, 11, None, true)
+            .unwrap();
         let data = mock_file.get_ref();
         assert!(!data.starts_with(initial));
         assert_eq!(data.len(), 512);
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn overwrite_preallocated() {
+        use ::std::os::unix::io::AsRawFd;
+
+        let dir = ::tempfile::tempdir().unwrap();
+        let path = dir.path().join("prealloc.file");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&[b'x'; 1000]).unwrap();
+        let res =
+            unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, 1024 * 1024) };
+        drop(file);
+        let summary = repeatedly_overwrite(
+            &path,
+            2,
+            SparseMode::Densify,
+            &mut OverwriteBuffer::new(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(summary.data_bytes, 1000);
+        let data = ::std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), 1000);
+        assert!(!data.starts_with(b"xxxxxxxx"));
+        if res == 0
+            && allocated_end(&File::open(&path).unwrap(), true)
+                .unwrap()
+                .is_some()
+        {
+            assert!(summary.beyond_eof_bytes >= 1024 * 1024 - 1000);
+        }
+    }
+
+    #[test]
+    fn overwrite_keeps_length() {
+        let dir = ::tempfile::tempdir().unwrap();
+        let path = dir.path().join("partial_block.file");
+        ::std::fs::write(&path, [b'x'; 1000]).unwrap();
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        let summary = overwrite_file(
+            &mut file,
+            2,
+            SparseMode::Densify,
+            &mut OverwriteBuffer::new(),
+            true,
+        )
+        .unwrap();
+        assert_eq!(summary.data_bytes, 1000);
+        assert_eq!(summary.beyond_eof_bytes, 0);
+        let data = ::std::fs::read(&path).unwrap();
+        assert_eq!(data.len(), 1000);
+        assert!(!data.starts_with(b"xxxxxxxx"));
+    }
+
+    #[test]
+    fn patterns_end_random() {
+        assert_eq!(pass_patterns(0), vec![]);
+        assert_eq!(pass_patterns(1), vec![None]);
+        assert_eq!(pass_patterns(3), vec![Some(0), Some(255), None]);
+        assert_eq!(
+            pass_patterns(7),
+            vec![Some(0), Some(255), Some(85), Some(170), None, None, None]
+        );
+    }
 }
@@ -0,0 +1,192 @@
+use ::std::fs;
+use ::std::fs::Metadata;
+use ::std::fs::Permissions;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::util::errors::add_err;
+use crate::util::escape::escape_path;
+use crate::util::privilege::is_root;
+use crate::util::ShredResult;
+
+/// Whether the owner can write, and whether the current user is the owner.
+#[cfg(unix)]
+fn owner_access(meta: &Metadata) -> (bool, bool) {
+    use ::std::os::unix::fs::MetadataExt;
+    let is_owner = meta.uid() == unsafe { libc::geteuid() };
+    (meta.mode() & 0o200 != 0, is_owner)
+}
+
+#[cfg(not(unix))]
+fn owner_access(meta: &Metadata) -> (bool, bool) {
+    (!meta.permissions().readonly(), true)
+}
+
+#[cfg(unix)]
+fn with_owner_write(perms: &Permissions) -> Permissions {
+    use ::std::os::unix::fs::PermissionsExt;
+    Permissions::from_mode(perms.mode() | 0o200)
+}
+
+#[cfg(not(unix))]
+fn with_owner_write(perms: &Permissions) -> Permissions {
+    let mut perms = perms.clone();
+    perms.set_readonly(false);
+    perms
+}
+
+/// Permissions changed by [make_writable], which are put back when this is dropped, so that
+/// they are also restored when shredding fails halfway. Use [WritableGuard::restore] to see errors.
+#[derive(Debug)]
+pub struct WritableGuard {
+    path: PathBuf,
+    original: Option<Permissions>,
+    verbose: bool,
+}
+
+impl WritableGuard {
+    /// Nothing was changed, so there is nothing to restore.
+    pub fn unchanged(path: &Path) -> Self {
+        WritableGuard {
+            path: path.to_owned(),
+            original: None,
+            verbose: false,
+        }
+    }
+
+    pub fn changed(&self) -> bool {
+        self.original.is_some()
+    }
+
+    /// Put back the original permissions now. Returns whether they had been changed.
+    pub fn restore(mut self) -> ShredResult<bool> {
+        let Some(original) = self.original.take() else {
+            return Ok(false);
+        };
+        match fs::set_permissions(&self.path, original) {
+            Ok(()) => Ok(true),
+            Err(err) => Err(add_err(
+                format!(
+                    "could not restore permissions of '{}'",
+                    escape_path(&self.path)
+                ),
+                self.verbose,
+                err,
+            )),
+        }
+    }
+
+    /// Leave the permissions as they are now, e.g. because the file is about to be removed.
+    pub fn dismiss(mut self) {
+        self.original = None;
+    }
+}
+
+impl Drop for WritableGuard {
+    fn drop(&mut self) {
+        if let Some(original) = self.original.take() {
+            let _ = fs::set_permissions(&self.path, original);
+        }
+    }
+}
+
+/// Add write permission for the owner if it is missing, so that a read-only file can be
+/// overwritten, or files in a read-only directory renamed and removed. Refuses paths that
+/// belong to someone else, unless running as root. The original permissions are restored
+/// when the returned guard is dropped.
+pub fn make_writable(path: &Path, verbose: bool) -> ShredResult<WritableGuard> {
+    make_writable_as(path, is_root(), verbose)
+}
+
+fn make_writable_as(path: &Path, root: bool, verbose: bool) -> ShredResult<WritableGuard> {
+    let meta = match fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(err) => {
+            return Err(add_err(
+                format!("could not read permissions of '{}'", escape_path(path)),
+                verbose,
+                err,
+            ))
+        }
+    };
+    let (writable, is_owner) = owner_access(&meta);
+    // Root can write regardless of permissions.
+    if writable || root {
+        return Ok(WritableGuard::unchanged(path));
+    }
+    if !is_owner {
+        return Err(format!(
+            "'{}' is read-only and owned by another user, so it will not be made writable",
+            escape_path(path)
+        ));
+    }
+    let original = meta.permissions();
+    if let Err(err) = fs::set_permissions(path, with_owner_write(&original)) {
+        return Err(add_err(
+            format!("could not make '{}' writable", escape_path(path)),
+            verbose,
+            err,
+        ));
+    }
+    Ok(WritableGuard {
+        path: path.to_owned(),
+        original: Some(original),
+        verbose,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    fn read_only_file(dir: &Path) -> PathBuf {
+        let path = dir.join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let mut perms = fs::metadata(&path).unwrap().permissions();
+        perms.set_readonly(true);
+        fs::set_permissions(&path, perms).unwrap();
+        path
+    }
+
+    #[test]
+    fn writable_file_unchanged() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        assert!(!make_writable(&path, true).unwrap().changed());
+    }
+
+    #[test]
+    fn read_only_file_unchanged_for_root() {
+        let dir = tempdir().unwrap();
+        let path = read_only_file(dir.path());
+        assert!(!make_writable_as(&path, true, true).unwrap().changed());
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+    }
+
+    // Root can change permissions of its own files too, so pretend not to be root,
+    // otherwise this would pass without changing anything.
+    #[test]
+    fn read_only_file_made_writable() {
+        let dir = tempdir().unwrap();
+        let path = read_only_file(dir.path());
+        let guard = make_writable_as(&path, false, true).unwrap();
+        assert!(guard.changed());
+        assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(guard.restore().unwrap());
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+    }
+
+    #[test]
+    fn restore_on_drop() {
+        let dir = tempdir().unwrap();
+        let path = read_only_file(dir.path());
+        {
+            let _guard = make_writable_as(&path, false, true).unwrap();
+            assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+        }
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+    }
+}
@@ -1,8 +1,11 @@
 use ::std::fs;
+use ::std::fs::File;
 use ::std::fs::OpenOptions;
 use ::std::path::Path;
 
+use crate::erase::discard::parent_dir;
 use crate::util::errors::add_err;
+use crate::util::escape::escape_path;
 use crate::ShredResult;
 
 pub fn truncate_file(path: &Path, verbose: bool) -> ShredResult<()> {
@@ -16,7 +19,12 @@ pub fn truncate_file(path: &Path, verbose: bool) -> ShredResult<()> {
             ))
         }
     };
-    if let Err(err) = file.set_len(0) {
+    truncate_handle(&file, 0, verbose)
+}
+
+/// Truncate a file that is already open for writing to the given length.
+pub fn truncate_handle(file: &File, len: u64, verbose: bool) -> ShredResult<()> {
+    if let Err(err) = file.set_len(len) {
         return Err(add_err(
             "failed to truncation file".to_owned(),
             verbose,
@@ -32,10 +40,53 @@ pub fn remove_file(path: &Path, verbose: bool) -> ShredResult<()> {
         Err(err) => Err(add_err(
             format!(
                 "could not remove file '{}' because remove operation failed",
-                &path.to_string_lossy()
+                escape_path(path)
             ),
             verbose,
             err,
         )),
     }
 }
+
+/// Persist the directory containing the path, so that renames and removals survive a power loss.
+#[cfg(unix)]
+pub fn sync_parent_dir(path: &Path, verbose: bool) -> ShredResult<()> {
+    let dir = parent_dir(path);
+    let res = fs::File::open(dir).and_then(|handle| handle.sync_all());
+    match res {
+        Ok(()) => Ok(()),
+        Err(err) => Err(add_err(
+            format!("could not persist directory '{}'", escape_path(dir)),
+            verbose,
+            err,
+        )),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn sync_parent_dir(_path: &Path, _verbose: bool) -> ShredResult<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn sync_dir_of_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        remove_file(&path, true).unwrap();
+        sync_parent_dir(&path, true).unwrap();
+    }
+
+    #[test]
+    fn sync_missing_dir() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("missing/file.txt");
+        assert!(sync_parent_dir(&path, true).is_err());
+    }
+}
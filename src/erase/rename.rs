@@ -1,23 +1,52 @@
+use crate::erase::remove::sync_parent_dir;
 use crate::util::errors::add_err;
 use crate::util::ShredResult;
-use ::base64::prelude::*;
+use ::rand::distr::Alphanumeric;
+use ::rand::Rng;
 use ::sha2::Digest;
 use ::sha2::Sha256;
+use ::std::ffi::OsStr;
 use ::std::fs;
+use ::std::ops::RangeInclusive;
 use ::std::path::Path;
 use ::std::path::PathBuf;
 
-fn generate_name(name: &str, number: u32) -> String {
-    let mut hash = Sha256::new();
-    hash.update(name.as_bytes());
-    hash.update(number.to_le_bytes());
-    let str = BASE64_URL_SAFE_NO_PAD.encode(hash.finalize());
-    format!("tmp{}", &str[..20])
+const ALPHANUMERIC: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Generate a name that is unrelated to the original one.
+/// Without seed, names are random and vary in length, so they cannot be linked to the original
+/// file. With a seed, names are derived from the seed and previous name, for reproducible tests.
+pub fn generate_name(name: &OsStr, number: u32, seed: Option<u64>) -> String {
+    match seed {
+        None => {
+            let mut rng = rand::rng();
+            let len = rng.random_range(12..=24);
+            (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(len)
+                .map(char::from)
+                .collect()
+        }
+        Some(seed) => {
+            let mut hash = Sha256::new();
+            hash.update(seed.to_le_bytes());
+            hash.update(name.as_encoded_bytes());
+            hash.update(number.to_le_bytes());
+            hash.finalize()[..20]
+                .iter()
+                .map(|byte| ALPHANUMERIC[*byte as usize % ALPHANUMERIC.len()] as char)
+                .collect()
+        }
+    }
 }
 
+/// Rename the file a number of times to unrelated names. The directory is persisted afterwards,
+/// or after every rename if `sync_each` is set (slower, but each name reaches the disk).
 pub fn repeatedly_rename_file(
     original_pth: &Path,
     reps: u32,
+    seed: Option<u64>,
+    sync_each: bool,
     verbose: bool,
 ) -> ShredResult<PathBuf> {
     let mut renamed = reps;
@@ -25,11 +54,8 @@ pub fn repeatedly_rename_file(
     for iter in 0..100 * reps {
         let new_path = {
             let mut p = old_path.clone();
-            let base_name = match old_path.file_name() {
-                Some(name) => name.to_str().expect("filename must be utf8"),
-                None => "",
-            };
-            p.set_file_name(generate_name(base_name, iter));
+            let base_name = old_path.file_name().unwrap_or_default();
+            p.set_file_name(generate_name(base_name, iter, seed));
             p
         };
         if new_path.exists() {
@@ -46,11 +72,90 @@ pub fn repeatedly_rename_file(
             }
         }
         old_path = new_path;
+        if sync_each {
+            sync_parent_dir(&old_path, verbose)?;
+        }
         renamed -= 1;
         if renamed == 0 {
             break;
         }
     }
+    if !sync_each {
+        sync_parent_dir(&old_path, verbose)?;
+    }
+    Ok(old_path)
+}
+
+/// Characters used for shrinking names, in the order they are tried (same as GNU shred).
+const NAME_CHARS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz_.";
+/// Number of names of one length to try before moving on to a shorter name.
+const MAX_COLLISIONS: u32 = 100;
+
+/// Change the name to the next one in odometer order, or return false if all names were tried.
+fn increment_name(name: &mut [u8]) -> bool {
+    for pos in (0..name.len()).rev() {
+        let index = NAME_CHARS.iter().position(|c| *c == name[pos]).unwrap_or(0);
+        if index + 1 < NAME_CHARS.len() {
+            name[pos] = NAME_CHARS[index + 1];
+            return true;
+        }
+        name[pos] = NAME_CHARS[0];
+    }
+    false
+}
+
+/// Rename the file like GNU shred: first to a name of zeros as long as the original name,
+/// then to shorter and shorter names, down to a single character. This overwrites the name
+/// in the directory entry, and the final name does not reveal the original length.
+/// The directory is persisted afterwards, or after every rename if `sync_each` is set.
+pub fn shrinking_rename_file(
+    original_pth: &Path,
+    sync_each: bool,
+    verbose: bool,
+) -> ShredResult<PathBuf> {
+    let name_len = original_pth.file_name().map(|name| name.len()).unwrap_or(0);
+    shrinking_rename_lengths(original_pth, 1..=name_len, sync_each, verbose)
+}
+
+/// Like [shrinking_rename_file], but only renames to names with lengths in the range,
+/// longest first.
+pub fn shrinking_rename_lengths(
+    original_pth: &Path,
+    lengths: RangeInclusive<usize>,
+    sync_each: bool,
+    verbose: bool,
+) -> ShredResult<PathBuf> {
+    let mut old_path = original_pth.to_owned();
+    for len in lengths.rev() {
+        let mut name = vec![NAME_CHARS[0]; len];
+        for _ in 0..MAX_COLLISIONS {
+            let new_path = old_path.with_file_name(String::from_utf8_lossy(&name).as_ref());
+            let is_special = name == b"." || name == b"..";
+            if !is_special && !new_path.exists() {
+                match fs::rename(&old_path, &new_path) {
+                    Ok(()) => {}
+                    Err(err) => {
+                        return Err(add_err(
+                            "failed to rename file during shredding",
+                            verbose,
+                            err,
+                        ))
+                    }
+                }
+                old_path = new_path;
+                if sync_each {
+                    sync_parent_dir(&old_path, verbose)?;
+                }
+                break;
+            }
+            if !increment_name(&mut name) {
+                break;
+            }
+        }
+    }
+    if !sync_each {
+        sync_parent_dir(&old_path, verbose)?;
+    }
     Ok(old_path)
 }
 
@@ -60,26 +165,27 @@ mod tests {
 
     use super::*;
 
+    fn make_collision_file(dir: &Path, name: &str) {
+        let mut path = dir.to_owned();
+        path.push(name);
+        let data = format!("collision data at {}", path.to_string_lossy());
+        fs::write(&path, data).unwrap();
+    }
+
     #[test]
     fn rename() {
         let data = b"hello world, this is test data";
         let temp_handle = tempdir().unwrap();
         let mut path = temp_handle.path().to_owned();
         path.push("original.file");
-        fs::write(&path, &data).unwrap();
-        let new_pth = repeatedly_rename_file(&path, 5, true).unwrap();
-        assert_eq!("tmpBkn0XzxIhgz0iWJWFT_V", new_pth.file_name().unwrap());
-        assert_eq!(&*data, fs::read(new_pth).unwrap().as_slice());
+        fs::write(&path, data).unwrap();
+        let new_pth = repeatedly_rename_file(&path, 5, Some(42), true, true).unwrap();
+        assert_eq!("AcS5tdvIw6mYjPRKtujZ", new_pth.file_name().unwrap());
+        assert_eq!(data, fs::read(new_pth).unwrap().as_slice());
     }
 
     #[test]
     fn rename_collision() {
-        fn make_collision_file(dir: &Path, name: &str) {
-            let mut path = dir.to_owned();
-            path.push(name);
-            let data = format!("collision data at {}", path.to_string_lossy());
-            fs::write(&path, &data).unwrap();
-        }
         let data = b"hello world, this is test data";
         let temp_handle = tempdir().unwrap();
         let mut path = temp_handle.path().to_owned();
@@ -88,9 +194,65 @@ mod tests {
         make_collision_file(&path, "_.tmp");
         make_collision_file(&path, "aa.tmp");
         path.push("original.file");
-        fs::write(&path, &data).unwrap();
-        let new_pth = repeatedly_rename_file(&path, 30, true).unwrap();
-        assert_eq!("tmpN47WM5FoYFZzHy_xCspx", new_pth.file_name().unwrap());
-        assert_eq!(&*data, fs::read(new_pth).unwrap().as_slice());
+        fs::write(&path, data).unwrap();
+        let new_pth = repeatedly_rename_file(&path, 30, Some(42), false, true).unwrap();
+        assert_eq!("ghzfEgXoNNxuDVdY1KYv", new_pth.file_name().unwrap());
+        assert_eq!(data, fs::read(new_pth).unwrap().as_slice());
+    }
+
+    #[test]
+    fn rename_random() {
+        let temp_handle = tempdir().unwrap();
+        let path = temp_handle.path().join("original.file");
+        fs::write(&path, b"data").unwrap();
+        let first = repeatedly_rename_file(&path, 3, None, false, true).unwrap();
+        fs::write(&path, b"data").unwrap();
+        let second = repeatedly_rename_file(&path, 3, None, false, true).unwrap();
+        assert_ne!(first, second);
+        let name = first.file_name().unwrap().to_str().unwrap();
+        assert!(name.len() >= 12 && name.len() <= 24);
+        assert!(name.bytes().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn increment() {
+        let mut name = b"00".to_vec();
+        assert!(increment_name(&mut name));
+        assert_eq!(name, b"01");
+        let mut name = b"0.".to_vec();
+        assert!(increment_name(&mut name));
+        assert_eq!(name, b"10");
+        let mut name = b"..".to_vec();
+        assert!(!increment_name(&mut name));
+    }
+
+    #[test]
+    fn rename_shrinking() {
+        let data = b"hello world, this is test data";
+        let temp_handle = tempdir().unwrap();
+        let mut path = temp_handle.path().to_owned();
+        path.push("original.file");
+        fs::write(&path, data).unwrap();
+        let new_pth = shrinking_rename_file(&path, false, true).unwrap();
+        assert_eq!("0", new_pth.file_name().unwrap());
+        assert_eq!(data, fs::read(new_pth).unwrap().as_slice());
+        assert_eq!(fs::read_dir(temp_handle.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn rename_shrinking_collision() {
+        let data = b"hello world, this is test data";
+        let temp_handle = tempdir().unwrap();
+        let mut path = temp_handle.path().to_owned();
+        make_collision_file(&path, "0");
+        make_collision_file(&path, "1");
+        make_collision_file(&path, "00");
+        make_collision_file(&path, "0000");
+        path.push("abcd");
+        fs::write(&path, data).unwrap();
+        let new_pth = shrinking_rename_file(&path, false, true).unwrap();
+        assert_eq!("2", new_pth.file_name().unwrap());
+        assert_eq!(data, fs::read(new_pth).unwrap().as_slice());
+        assert_eq!(fs::read_dir(temp_handle.path()).unwrap().count(), 5);
     }
 }
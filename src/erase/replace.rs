@@ -0,0 +1,292 @@
+use ::std::ffi::OsStr;
+use ::std::fs;
+use ::std::fs::File;
+use ::std::fs::Metadata;
+use ::std::fs::OpenOptions;
+use ::std::io;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::config::conf::ShredConfig;
+use crate::erase::discard::parent_dir;
+use crate::erase::handle::{remove_shredded, shred_handle};
+use crate::erase::rename::generate_name;
+use crate::erase::report::FileReport;
+use crate::erase::temp_file::ShreddedTempFile;
+use crate::util::errors::add_err;
+use crate::util::errors::wrap_io;
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Number of names the file has, since overwriting it would also destroy the data of other hard links.
+#[cfg(unix)]
+fn link_count(meta: &Metadata) -> u64 {
+    use ::std::os::unix::fs::MetadataExt;
+    meta.nlink()
+}
+
+#[cfg(not(unix))]
+fn link_count(_meta: &Metadata) -> u64 {
+    1
+}
+
+/// Options for shredding the old data; the files are always removed.
+fn shred_options<P: AsRef<Path>>(config: &ShredConfig<P>) -> ShredResult<ShredConfig<PathBuf>> {
+    config.validate()?;
+    let mut options = config.options();
+    options.keep_files = false;
+    options.confirmation_prompt = false;
+    options.progress_bar = false;
+    Ok(options)
+}
+
+/// Open a file for overwriting it later, refusing anything that is not a file with a single name.
+fn open_for_shredding(path: &Path, verbose: bool) -> ShredResult<(File, Metadata)> {
+    let file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            return Err(add_err(
+                format!("could not open '{}' for writing", escape_path(path)),
+                verbose,
+                err,
+            ))
+        }
+    };
+    let meta = wrap_io(|| "could not inspect file", file.metadata())?;
+    if !meta.is_file() {
+        return Err(format!("'{}' is not a file", escape_path(path)));
+    }
+    if link_count(&meta) > 1 {
+        return Err(format!(
+            "'{}' has other hard links, which would lose their data too",
+            escape_path(path)
+        ));
+    }
+    Ok((file, meta))
+}
+
+/// Give the new contents the owner of the old ones, which usually requires being root unless
+/// they are the same already.
+#[cfg(unix)]
+fn copy_owner(meta: &Metadata, replacement: &ShreddedTempFile) -> ShredResult<()> {
+    use ::std::os::unix::fs::fchown;
+    use ::std::os::unix::fs::MetadataExt;
+    let current = wrap_io(
+        || "could not inspect new contents",
+        replacement.as_file().metadata(),
+    )?;
+    if current.uid() == meta.uid() && current.gid() == meta.gid() {
+        return Ok(());
+    }
+    wrap_io(
+        || "could not copy owner to new contents",
+        fchown(replacement.as_file(), Some(meta.uid()), Some(meta.gid())),
+    )
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_meta: &Metadata, _replacement: &ShreddedTempFile) -> ShredResult<()> {
+    Ok(())
+}
+
+/// Create a file with a random hidden name next to the target, which is shredded if it is not
+/// moved into place.
+fn sibling_temp_file<P: AsRef<Path>>(
+    target: &Path,
+    options: &ShredConfig<P>,
+) -> ShredResult<ShreddedTempFile> {
+    let name = format!(".{}.tmp", generate_name(OsStr::new(""), 0, None));
+    ShreddedTempFile::create(parent_dir(target).join(name))?.with_config(options)
+}
+
+/// Replace the contents of the file with those written by `new_contents`, then overwrite the old
+/// contents. The new contents are written to a sibling file, persisted, and atomically moved over
+/// the original, so at every moment either the old or new version exists. The old data is then
+/// overwritten through a handle that was opened before the move, so it cannot be confused with
+/// the new file. Files with hard links are refused, since their data would be destroyed too.
+/// Permissions and owner are copied to the new contents (failing if the owner cannot be set),
+/// but extended attributes and ACLs are not.
+pub fn replace_contents<P: AsRef<Path>>(
+    path: &Path,
+    new_contents: impl FnOnce(&mut File) -> io::Result<()>,
+    config: &ShredConfig<P>,
+) -> ShredResult<FileReport> {
+    let options = shred_options(config)?;
+    let verbose = options.verbosity.debug();
+    let mut report = FileReport::new(path);
+    let (mut original, meta) = open_for_shredding(path, verbose)?;
+
+    let mut replacement = sibling_temp_file(path, &options)?;
+    wrap_io(
+        || "could not write new contents",
+        new_contents(replacement.as_file_mut()),
+    )?;
+    wrap_io(
+        || "could not copy permissions to new contents",
+        fs::set_permissions(replacement.path(), meta.permissions()),
+    )?;
+    copy_owner(&meta, &replacement)?;
+    replacement.persist(path)?;
+    report.note("replaced contents atomically");
+
+    let written = shred_handle(&mut original, &options)?;
+    report.note(format!(
+        "overwrote {} bytes of old contents {} times, then truncated them",
+        written.data_bytes, options.overwrite_count
+    ));
+    Ok(report)
+}
+
+/// Write a new file at `target` from the contents of `source` (e.g. encrypted), and then shred
+/// `source`. The target is written to a sibling file and linked into place once complete, so there
+/// is always either the source, or a complete target. Fails if the target already exists, even
+/// if it is created while transforming. The source is overwritten through the handle it was read
+/// from, and only removed if it is still at its path.
+pub fn transform_to<P: AsRef<Path>>(
+    source: &Path,
+    target: &Path,
+    transform: impl FnOnce(&mut File, &mut File) -> io::Result<()>,
+    config: &ShredConfig<P>,
+) -> ShredResult<FileReport> {
+    let options = shred_options(config)?;
+    let verbose = options.verbosity.debug();
+    // Only to fail early; persisting checks again without a race.
+    if target.exists() {
+        return Err(format!(
+            "'{}' already exists, so it will not be replaced",
+            escape_path(target)
+        ));
+    }
+    let mut report = FileReport::new(source);
+    let (mut source_file, _) = open_for_shredding(source, verbose)?;
+    let mut output = sibling_temp_file(target, &options)?;
+    wrap_io(
+        || "could not write new contents",
+        transform(&mut source_file, output.as_file_mut()),
+    )?;
+    output.persist_new(target)?;
+    report.note(format!("new contents written to '{}'", escape_path(target)));
+
+    let written = shred_handle(&mut source_file, &options)?;
+    report.note(format!(
+        "overwrote {} bytes {} times, then truncated them",
+        written.data_bytes, options.overwrite_count
+    ));
+    remove_shredded(source, source_file, &options)?;
+    report.note("removed");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::io::Read;
+    use ::std::io::Write;
+
+    use ::tempfile::tempdir;
+
+    use crate::config::typ::Verbosity;
+
+    use super::*;
+
+    fn config() -> ShredConfig<PathBuf> {
+        ShredConfig::non_interactive(vec![], Verbosity::Debug, false, 2, 2)
+    }
+
+    #[test]
+    fn replace_keeps_only_new_contents() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"plaintext").unwrap();
+        let report =
+            replace_contents(&path, |file| file.write_all(b"ciphertext"), &config()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"ciphertext");
+        assert!(report.notes[1].contains("overwrote 9 bytes"));
+        assert_eq!(dir.path().read_dir().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn failed_write_keeps_original() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"plaintext").unwrap();
+        let res = replace_contents(
+            &path,
+            |file| {
+                file.write_all(b"cipher")?;
+                Err(io::Error::other("encryption failed"))
+            },
+            &config(),
+        );
+        assert!(res.unwrap_err().contains("encryption failed"));
+        assert_eq!(fs::read(&path).unwrap(), b"plaintext");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refuse_hard_links() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"plaintext").unwrap();
+        fs::hard_link(&path, dir.path().join("link.txt")).unwrap();
+        let res = replace_contents(&path, |file| file.write_all(b"ciphertext"), &config());
+        assert!(res.unwrap_err().contains("hard links"));
+        assert_eq!(fs::read(&path).unwrap(), b"plaintext");
+    }
+
+    #[test]
+    fn transform_then_shred_source() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("file.txt");
+        let target = dir.path().join("file.txt.enc");
+        fs::write(&source, b"plaintext").unwrap();
+        transform_to(
+            &source,
+            &target,
+            |input, output| {
+                let mut data = vec![];
+                input.read_to_end(&mut data)?;
+                data.reverse();
+                output.write_all(&data)
+            },
+            &config(),
+        )
+        .unwrap();
+        assert!(!source.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"txetnialp");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 1);
+    }
+
+    #[test]
+    fn transform_keeps_target_created_meanwhile() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("file.txt");
+        let target = dir.path().join("other.txt");
+        fs::write(&source, b"plaintext").unwrap();
+        let res = transform_to(
+            &source,
+            &target,
+            |_, output| {
+                fs::write(&target, b"other")?;
+                output.write_all(b"new")
+            },
+            &config(),
+        );
+        assert!(res.unwrap_err().contains("already exists"));
+        assert_eq!(fs::read(&target).unwrap(), b"other");
+        assert_eq!(fs::read(&source).unwrap(), b"plaintext");
+        assert_eq!(dir.path().read_dir().unwrap().count(), 2);
+    }
+
+    #[test]
+    fn transform_refuses_existing_target() {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("file.txt");
+        let target = dir.path().join("other.txt");
+        fs::write(&source, b"plaintext").unwrap();
+        fs::write(&target, b"other").unwrap();
+        let res = transform_to(&source, &target, |_, _| Ok(()), &config());
+        assert!(res.unwrap_err().contains("already exists"));
+        assert!(source.exists());
+    }
+}
@@ -0,0 +1,35 @@
+use ::std::fmt;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::util::escape::escape_path;
+
+/// Summary of what was done to a single file, to show to the user afterwards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub notes: Vec<String>,
+}
+
+impl FileReport {
+    pub fn new(path: &Path) -> Self {
+        FileReport {
+            path: path.to_owned(),
+            notes: vec![],
+        }
+    }
+
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", escape_path(&self.path))?;
+        for note in &self.notes {
+            write!(f, "\n  - {}", note)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use ::std::fs;
+use ::std::io;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::config::typ::ScratchDir;
+use crate::erase::discard::parent_dir;
+use crate::erase::remove::sync_parent_dir;
+use crate::util::errors::add_err;
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Name of the scratch directory created at the root of a filesystem.
+const SCRATCH_NAME: &str = ".shred-tmp";
+
+/// Filesystem device of the path, or `None` if the platform cannot tell.
+#[cfg(unix)]
+fn device_of(path: &Path) -> io::Result<Option<u64>> {
+    use ::std::os::unix::fs::MetadataExt;
+    Ok(Some(fs::metadata(path)?.dev()))
+}
+
+#[cfg(not(unix))]
+fn device_of(path: &Path) -> io::Result<Option<u64>> {
+    fs::metadata(path)?;
+    Ok(None)
+}
+
+/// Highest ancestor of the directory that is still on the same filesystem.
+fn filesystem_root(dir: &Path) -> io::Result<PathBuf> {
+    let mut root = fs::canonicalize(dir)?;
+    let dev = device_of(&root)?;
+    if dev.is_none() {
+        return Ok(root
+            .ancestors()
+            .last()
+            .map(Path::to_path_buf)
+            .unwrap_or(root));
+    }
+    while let Some(parent) = root.parent() {
+        if device_of(parent)? != dev {
+            break;
+        }
+        root = parent.to_path_buf();
+    }
+    Ok(root)
+}
+
+/// Find or create the scratch directory for a file, or explain why it cannot be used.
+fn prepare_scratch_dir(file: &Path, scratch: &ScratchDir) -> Result<Option<PathBuf>, String> {
+    let dir = match scratch {
+        ScratchDir::InPlace => return Ok(None),
+        ScratchDir::FilesystemRoot => filesystem_root(parent_dir(file))
+            .map_err(|err| format!("could not find filesystem root: {}", err))?
+            .join(SCRATCH_NAME),
+        ScratchDir::Dir(dir) => dir.clone(),
+    };
+    if let Err(err) = fs::create_dir(&dir) {
+        if err.kind() != io::ErrorKind::AlreadyExists {
+            return Err(format!(
+                "could not create scratch directory '{}': {}",
+                escape_path(&dir),
+                err
+            ));
+        }
+    }
+    if !dir.is_dir() {
+        return Err(format!(
+            "scratch path '{}' is not a directory",
+            escape_path(&dir)
+        ));
+    }
+    let file_dev = device_of(file).map_err(|err| format!("could not inspect file: {}", err))?;
+    let dir_dev = device_of(&dir).map_err(|err| {
+        format!(
+            "could not inspect scratch directory '{}': {}",
+            escape_path(&dir),
+            err
+        )
+    })?;
+    if file_dev != dir_dev {
+        return Err(format!(
+            "scratch directory '{}' is on a different filesystem",
+            escape_path(&dir)
+        ));
+    }
+    Ok(Some(dir))
+}
+
+/// Move the file into the scratch directory, keeping its (already scrambled) name, so that the
+/// remaining renames and the removal do not happen in the original directory. If the scratch
+/// directory cannot be used, the file stays where it is and the reason is returned, so the
+/// caller can note the fallback. A scratch directory that had to be created is left behind,
+/// so that later runs can use it without changing the filesystem root every time.
+pub fn move_to_scratch(
+    file: &Path,
+    scratch: &ScratchDir,
+    verbose: bool,
+) -> ShredResult<(PathBuf, Option<String>)> {
+    let dir = match prepare_scratch_dir(file, scratch) {
+        Ok(Some(dir)) => dir,
+        Ok(None) => return Ok((file.to_path_buf(), None)),
+        Err(reason) => return Ok((file.to_path_buf(), Some(reason))),
+    };
+    let Some(name) = file.file_name() else {
+        return Ok((file.to_path_buf(), Some("file has no name".to_owned())));
+    };
+    let target = dir.join(name);
+    // Link and unlink instead of renaming, since a rename would replace a file that appears
+    // in the scratch directory under the same name.
+    match fs::hard_link(file, &target) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+            return Ok((
+                file.to_path_buf(),
+                Some(format!(
+                    "'{}' already exists in scratch directory",
+                    escape_path(Path::new(name))
+                )),
+            ))
+        }
+        // Still a different device (e.g. bind mounts), no hard links, or not allowed.
+        Err(err) => {
+            return Ok((
+                file.to_path_buf(),
+                Some(add_err(
+                    format!("could not move into '{}'", escape_path(&dir)),
+                    verbose,
+                    err,
+                )),
+            ))
+        }
+    }
+    if let Err(err) = fs::remove_file(file) {
+        let _ = fs::remove_file(&target);
+        return Err(add_err(
+            format!(
+                "could not remove '{}' after linking it into the scratch directory",
+                escape_path(file)
+            ),
+            verbose,
+            err,
+        ));
+    }
+    sync_parent_dir(file, verbose)?;
+    Ok((target, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn in_place_does_nothing() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let (moved, fallback) = move_to_scratch(&path, &ScratchDir::InPlace, true).unwrap();
+        assert_eq!(moved, path);
+        assert!(fallback.is_none());
+    }
+
+    #[test]
+    fn move_to_configured_dir() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let scratch = ScratchDir::Dir(dir.path().join("scratch"));
+        let (moved, fallback) = move_to_scratch(&path, &scratch, true).unwrap();
+        assert!(fallback.is_none());
+        assert_eq!(moved, dir.path().join("scratch/file.txt"));
+        assert!(!path.exists());
+        assert_eq!(fs::read(moved).unwrap(), b"data");
+    }
+
+    #[test]
+    fn keep_existing_file_in_scratch_dir() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        fs::create_dir(dir.path().join("scratch")).unwrap();
+        fs::write(dir.path().join("scratch/file.txt"), b"other").unwrap();
+        let scratch = ScratchDir::Dir(dir.path().join("scratch"));
+        let (moved, fallback) = move_to_scratch(&path, &scratch, true).unwrap();
+        assert_eq!(moved, path);
+        assert!(fallback.unwrap().contains("already exists"));
+        assert_eq!(
+            fs::read(dir.path().join("scratch/file.txt")).unwrap(),
+            b"other"
+        );
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+    }
+
+    #[test]
+    fn fall_back_if_unusable() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let scratch = ScratchDir::Dir(dir.path().join("missing/scratch"));
+        let (moved, fallback) = move_to_scratch(&path, &scratch, true).unwrap();
+        assert_eq!(moved, path);
+        assert!(fallback.unwrap().contains("could not create"));
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn root_is_ancestor() {
+        let dir = tempdir().unwrap();
+        let root = filesystem_root(dir.path()).unwrap();
+        assert!(fs::canonicalize(dir.path()).unwrap().starts_with(root));
+    }
+}
@@ -0,0 +1,518 @@
+use ::std::collections::BTreeSet;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+use ::std::sync::Arc;
+use ::std::sync::Mutex;
+use ::std::sync::MutexGuard;
+use ::std::sync::PoisonError;
+
+use ::indicatif::ProgressBar;
+#[cfg(feature = "async")]
+use ::tokio::sync::mpsc::UnboundedSender;
+
+use crate::config::conf::ShredConfig;
+use crate::erase::dirents::wipe_directory_entries;
+use crate::erase::discard::{parent_dir, trim_filesystems, DiscardOutcome};
+use crate::erase::metadata::{record_dir_times, restore_dir_times, DirTimes};
+use crate::erase::orchestrate::delete_file;
+use crate::erase::overwrite::OverwriteBuffer;
+use crate::erase::report::FileReport;
+use crate::inspect::collect::{inspect_file, FileInfo};
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Shreds files one at a time with the same options, so that files can be streamed in
+/// (e.g. from a directory walk) and their results handled as soon as each one is done.
+///
+/// Work that concerns the directories of the files (wiping entries, restoring times, trim)
+/// is done once per directory by [Shredder::finish].
+#[derive(Debug)]
+pub struct Shredder {
+    config: Arc<ShredConfig<PathBuf>>,
+    progress: Option<ProgressBar>,
+    /// Whether the length of the progress bar was set beforehand, instead of growing per file.
+    total_known: bool,
+    buffer: OverwriteBuffer,
+    /// Directories of the shredded files, only kept if [Shredder::finish] does something to them.
+    dirs: BTreeSet<PathBuf>,
+    dir_times: Arc<PendingDirTimes>,
+    #[cfg(feature = "async")]
+    events: Option<UnboundedSender<ShredEvent>>,
+}
+
+impl Shredder {
+    /// Check the options and keep them; the files of the config are ignored.
+    /// No confirmation is asked, that is up to the caller.
+    pub fn new<P: AsRef<Path>>(config: &ShredConfig<P>) -> ShredResult<Self> {
+        config.validate()?;
+        let progress = if config.progress_bar {
+            Some(ProgressBar::new(0))
+        } else {
+            None
+        };
+        Ok(Shredder {
+            config: Arc::new(config.options()),
+            progress,
+            total_known: false,
+            buffer: OverwriteBuffer::new(),
+            dirs: BTreeSet::new(),
+            dir_times: Arc::new(PendingDirTimes {
+                times: Mutex::new(vec![]),
+                verbose: config.verbosity.debug(),
+            }),
+            #[cfg(feature = "async")]
+            events: None,
+        })
+    }
+
+    /// Set the total size of the files that will be shredded, if known beforehand, so that the
+    /// progress bar shows how far along it is, instead of growing with each file.
+    pub fn with_total_size(mut self, total_kb: u64) -> Self {
+        if let Some(pb) = &self.progress {
+            pb.set_length(total_kb);
+        }
+        self.total_known = true;
+        self
+    }
+
+    /// Inspect and shred a single file.
+    pub fn shred_path(&mut self, path: &Path) -> ShredResult<FileReport> {
+        let info = inspect_file(path, &self.config).map_err(|problem| problem.to_string())?;
+        self.shred_info(&info)
+    }
+
+    /// Shred the files one by one, yielding each file with its result as soon as it is done.
+    /// Nothing happens until the iterator is advanced. A failure does not stop the other files.
+    pub fn shred_all<'s, P: AsRef<Path> + 's>(
+        &'s mut self,
+        files: impl IntoIterator<Item = P> + 's,
+    ) -> impl Iterator<Item = (P, ShredResult<FileReport>)> + 's {
+        files.into_iter().map(move |file| {
+            let result = self.shred_path(file.as_ref());
+            (file, result)
+        })
+    }
+
+    pub(crate) fn shred_info(&mut self, file: &FileInfo) -> ShredResult<FileReport> {
+        let dir = parent_dir(file.path);
+        if self.uses_dirs() && !self.dirs.contains(dir) {
+            // Record before anything in the directory changes.
+            if self.records_dir_times() {
+                let times = record_dir_times(dir, self.config.verbosity.debug())?;
+                self.dir_times.lock().push(times);
+            }
+            self.dirs.insert(dir.to_owned());
+        }
+        shred_with_progress(
+            file,
+            &self.config,
+            self.progress.as_ref(),
+            self.total_known,
+            &mut self.buffer,
+        )
+    }
+
+    fn records_dir_times(&self) -> bool {
+        self.config.restore_dir_times && !self.config.keep_files
+    }
+
+    /// Whether [Shredder::finish] does anything to the directories, so they need to be kept.
+    fn uses_dirs(&self) -> bool {
+        self.records_dir_times()
+            || (self.config.wipe_dirents && !self.config.keep_files)
+            || self.config.trim_filesystem
+    }
+
+    /// Finish the directories of all shredded files: wipe their unused entries and restore
+    /// their times, and trim their filesystems, as configured. Returns a report per directory.
+    pub fn finish(self) -> ShredResult<Vec<FileReport>> {
+        Ok(self.finish_dirs()?.reports())
+    }
+
+    pub(crate) fn finish_dirs(self) -> ShredResult<FinishedDirs> {
+        let verbose = self.config.verbosity.debug();
+        let mut finished = FinishedDirs {
+            dirs: self.dirs.iter().cloned().collect(),
+            ..FinishedDirs::default()
+        };
+        if self.config.wipe_dirents && !self.config.keep_files {
+            for dir in &self.dirs {
+                let count = wipe_directory_entries(dir, verbose)?;
+                finished.dirents.push((dir.clone(), count));
+            }
+        }
+        // Take them one by one, so that any left after a failure are restored on drop.
+        while let Some(times) = self.dir_times.take_first() {
+            restore_dir_times(&times, verbose)?;
+            finished.restored_times.push(times.dir.clone());
+        }
+        if self.config.trim_filesystem {
+            let dirs: Vec<_> = self.dirs.iter().map(PathBuf::as_path).collect();
+            finished.trims = trim_filesystems(&dirs, verbose)?;
+        }
+        if let Some(ref pb) = self.progress {
+            pb.finish_with_message("done");
+        }
+        Ok(finished)
+    }
+}
+
+fn report_of<'r>(reports: &'r mut [FileReport], dir: &Path) -> Option<&'r mut FileReport> {
+    reports.iter_mut().find(|report| report.path == dir)
+}
+
+/// What [Shredder::finish] did to the directories.
+#[derive(Debug, Default)]
+pub(crate) struct FinishedDirs {
+    dirs: Vec<PathBuf>,
+    dirents: Vec<(PathBuf, u64)>,
+    restored_times: Vec<PathBuf>,
+    trims: Vec<(PathBuf, DiscardOutcome)>,
+}
+
+impl FinishedDirs {
+    /// A report for each directory that something was done to.
+    pub(crate) fn reports(&self) -> Vec<FileReport> {
+        let mut reports: Vec<_> = self.dirs.iter().map(|dir| FileReport::new(dir)).collect();
+        for (dir, count) in &self.dirents {
+            if let Some(report) = report_of(&mut reports, dir) {
+                report.note(format!("overwrote {} directory entries", count));
+            }
+        }
+        for dir in &self.restored_times {
+            if let Some(report) = report_of(&mut reports, dir) {
+                report.note(
+                    "restored access and modification times (its change time cannot be restored)",
+                );
+            }
+        }
+        for (dir, outcome) in &self.trims {
+            if let Some(report) = report_of(&mut reports, dir) {
+                report.note(format!("trim of filesystem: {}", outcome));
+            }
+        }
+        reports.retain(|report| !report.notes.is_empty());
+        reports
+    }
+
+    /// Print a line for each thing that was done, in the format `shred` has always used.
+    pub(crate) fn print(&self) {
+        for (dir, count) in &self.dirents {
+            println!(
+                "overwrote {} directory entries in '{}'",
+                count,
+                escape_path(dir)
+            );
+        }
+        for dir in &self.restored_times {
+            println!(
+                "restored access and modification times of '{}' (its change time cannot be restored)",
+                escape_path(dir)
+            );
+        }
+        for (dir, outcome) in &self.trims {
+            println!(
+                "trim of filesystem containing '{}': {}",
+                escape_path(dir),
+                outcome
+            );
+        }
+    }
+}
+
+/// Directory times that [Shredder::finish] did not restore yet, e.g. because a file failed and
+/// the error was returned early. Work running in the background keeps a reference, so that the
+/// times are only restored on drop once nothing changes the directories anymore. Errors are
+/// ignored then, as there is no way to report them.
+#[derive(Debug)]
+struct PendingDirTimes {
+    times: Mutex<Vec<DirTimes>>,
+    verbose: bool,
+}
+
+impl PendingDirTimes {
+    fn lock(&self) -> MutexGuard<'_, Vec<DirTimes>> {
+        self.times.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn take_first(&self) -> Option<DirTimes> {
+        let mut times = self.lock();
+        if times.is_empty() {
+            return None;
+        }
+        Some(times.remove(0))
+    }
+}
+
+impl Drop for PendingDirTimes {
+    fn drop(&mut self) {
+        let times = self.times.get_mut().unwrap_or_else(PoisonError::into_inner);
+        for times in times.drain(..) {
+            let _ = restore_dir_times(&times, self.verbose);
+        }
+    }
+}
+
+fn shred_with_progress(
+    file: &FileInfo,
+    config: &ShredConfig<PathBuf>,
+    progress: Option<&ProgressBar>,
+    total_known: bool,
+    buffer: &mut OverwriteBuffer,
+) -> ShredResult<FileReport> {
+    if let Some(pb) = progress {
+        if !total_known {
+            pb.inc_length(file.size_kb);
+        }
+    }
+    let report = delete_file(file, config, buffer)?;
+    if let Some(pb) = progress {
+        pb.inc(file.size_kb);
+    }
+    Ok(report)
+}
+
+/// Progress of asynchronous shredding, sent as each phase completes.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShredEvent {
+    /// The file was inspected, and overwriting starts.
+    Started { path: PathBuf, size_kb: u64 },
+    /// The file was shredded.
+    Shredded(FileReport),
+    /// The file could not be shredded; it may be partially shredded if it had started.
+    Failed { path: PathBuf, error: String },
+    /// The directories were finished, see [Shredder::finish].
+    Finished(Vec<FileReport>),
+}
+
+/// Work is done on the blocking thread pool of `tokio`, one phase at a time: recording directory
+/// times, then inspecting and shredding a file, and at the end finishing the directories. When
+/// the future is dropped, the phase that is running still completes in the background (so a file
+/// is never left half-shredded by cancellation), but no further phases are started. Directory
+/// times are then restored once that phase is done, but unused directory entries are not wiped
+/// and filesystems not trimmed, as that needs [Shredder::finish_async].
+#[cfg(feature = "async")]
+impl Shredder {
+    /// Send an event to this channel after each phase.
+    pub fn with_events(mut self, events: UnboundedSender<ShredEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn send(events: Option<&UnboundedSender<ShredEvent>>, event: ShredEvent) {
+        if let Some(events) = events {
+            // The receiver may have lost interest, which should not stop shredding.
+            let _ = events.send(event);
+        }
+    }
+
+    /// Inspect and shred a single file, like [Shredder::shred_path], without blocking the runtime.
+    pub async fn shred_path_async(&mut self, path: &Path) -> ShredResult<FileReport> {
+        let path = path.to_owned();
+        let dir = parent_dir(&path).to_owned();
+        if self.uses_dirs() && !self.dirs.contains(&dir) {
+            if self.records_dir_times() {
+                let verbose = self.config.verbosity.debug();
+                let record_dir = dir.clone();
+                let times = run_blocking(move || record_dir_times(&record_dir, verbose)).await?;
+                self.dir_times.lock().push(times);
+            }
+            self.dirs.insert(dir);
+        }
+        let config = self.config.clone();
+        let progress = self.progress.clone();
+        let total_known = self.total_known;
+        let events = self.events.clone();
+        let failed_path = path.clone();
+        let pending_times = Arc::clone(&self.dir_times);
+        // The buffer is lent to the blocking task; if that does not return, a new one is used.
+        let mut buffer = ::std::mem::take(&mut self.buffer);
+        let (buffer, result) = run_blocking(move || {
+            // Restoring the times waits until this is done, also if the future is dropped.
+            let _pending_times = pending_times;
+            let result = inspect_file(&path, &*config)
+                .map_err(|problem| problem.to_string())
+                .and_then(|info| {
+                    Shredder::send(
+                        events.as_ref(),
+                        ShredEvent::Started {
+                            path: path.clone(),
+                            size_kb: info.size_kb,
+                        },
+                    );
+                    shred_with_progress(&info, &config, progress.as_ref(), total_known, &mut buffer)
+                });
+            Ok((buffer, result))
+        })
+        .await
+        .unwrap_or_else(|err| (OverwriteBuffer::new(), Err(err)));
+        self.buffer = buffer;
+        match &result {
+            Ok(report) => {
+                Shredder::send(self.events.as_ref(), ShredEvent::Shredded(report.clone()))
+            }
+            Err(error) => Shredder::send(
+                self.events.as_ref(),
+                ShredEvent::Failed {
+                    path: failed_path,
+                    error: error.clone(),
+                },
+            ),
+        }
+        result
+    }
+
+    /// Finish the directories like [Shredder::finish], without blocking the runtime.
+    pub async fn finish_async(self) -> ShredResult<Vec<FileReport>> {
+        let events = self.events.clone();
+        let reports = run_blocking(move || self.finish()).await?;
+        Shredder::send(events.as_ref(), ShredEvent::Finished(reports.clone()));
+        Ok(reports)
+    }
+}
+
+/// Run blocking work on the blocking thread pool of the current `tokio` runtime.
+#[cfg(feature = "async")]
+pub async fn run_blocking<T: Send + 'static>(
+    work: impl FnOnce() -> ShredResult<T> + Send + 'static,
+) -> ShredResult<T> {
+    match ::tokio::task::spawn_blocking(work).await {
+        Ok(result) => result,
+        Err(err) => Err(format!("shredding task failed: {}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs;
+
+    use ::filetime::{set_file_times, FileTime};
+    use ::tempfile::tempdir;
+
+    use crate::config::typ::Verbosity;
+
+    use super::*;
+
+    #[test]
+    fn shred_all_streams_results() {
+        let dir = tempdir().unwrap();
+        let present = dir.path().join("present.txt");
+        let missing = dir.path().join("missing.txt");
+        fs::write(&present, b"secret").unwrap();
+        let config = ShredConfig::<PathBuf>::builder()
+            .verbosity(Verbosity::Quiet)
+            .overwrite_count(2)
+            .rename_count(2)
+            .wipe_dirents(true)
+            .build()
+            .unwrap();
+        let mut shredder = Shredder::new(&config).unwrap();
+        let results: Vec<_> = shredder
+            .shred_all(vec![present.clone(), missing.clone()])
+            .collect();
+        assert_eq!(results[0].0, present);
+        assert!(results[0].1.is_ok());
+        assert_eq!(results[1].0, missing);
+        assert!(results[1]
+            .1
+            .as_ref()
+            .unwrap_err()
+            .contains("could not read"));
+        assert!(!present.exists());
+        let dir_reports = shredder.finish().unwrap();
+        assert_eq!(dir_reports.len(), 1);
+        assert!(dir_reports[0].notes[0].contains("directory entries"));
+    }
+
+    #[test]
+    fn dirs_only_kept_when_used() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"secret").unwrap();
+        let config = ShredConfig::<PathBuf>::builder()
+            .verbosity(Verbosity::Quiet)
+            .overwrite_count(1)
+            .build()
+            .unwrap();
+        let mut shredder = Shredder::new(&config).unwrap();
+        shredder.shred_path(&path).unwrap();
+        assert!(shredder.dirs.is_empty());
+        assert!(shredder.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn shred_path_keep() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"secret").unwrap();
+        let config = ShredConfig::<PathBuf>::builder()
+            .verbosity(Verbosity::Quiet)
+            .keep_files(true)
+            .overwrite_count(1)
+            .build()
+            .unwrap();
+        let mut shredder = Shredder::new(&config).unwrap();
+        shredder.shred_path(&path).unwrap();
+        assert_ne!(fs::read(&path).unwrap(), b"secret");
+        assert!(shredder.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn restore_dir_times_on_drop() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"secret").unwrap();
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        set_file_times(dir.path(), old, old).unwrap();
+        let config = ShredConfig::<PathBuf>::builder()
+            .verbosity(Verbosity::Quiet)
+            .overwrite_count(1)
+            .restore_dir_times(true)
+            .build()
+            .unwrap();
+        let mut shredder = Shredder::new(&config).unwrap();
+        shredder.shred_path(&path).unwrap();
+        assert!(shredder
+            .shred_path(&dir.path().join("missing.txt"))
+            .is_err());
+        drop(shredder);
+        let restored = fs::metadata(dir.path()).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&restored), old);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn shred_path_async_sends_events() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("upload.bin");
+        fs::write(&path, b"secret").unwrap();
+        let config = ShredConfig::<PathBuf>::builder()
+            .verbosity(Verbosity::Quiet)
+            .overwrite_count(1)
+            .restore_dir_times(true)
+            .build()
+            .unwrap();
+        let (sender, mut receiver) = ::tokio::sync::mpsc::unbounded_channel();
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut shredder = Shredder::new(&config).unwrap().with_events(sender);
+            shredder.shred_path_async(&path).await.unwrap();
+            let missing = dir.path().join("missing.bin");
+            assert!(shredder.shred_path_async(&missing).await.is_err());
+            shredder.finish_async().await.unwrap();
+        });
+        assert!(!path.exists());
+        let mut events = vec![];
+        while let Ok(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(events.len(), 4);
+        assert!(matches!(&events[0], ShredEvent::Started { size_kb: 1, .. }));
+        assert!(matches!(&events[1], ShredEvent::Shredded(report) if report.path == path));
+        assert!(matches!(&events[2], ShredEvent::Failed { .. }));
+        assert!(matches!(&events[3], ShredEvent::Finished(reports) if reports.len() == 1));
+    }
+}
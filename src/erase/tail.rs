@@ -0,0 +1,98 @@
+use ::std::fs::OpenOptions;
+
+use crate::config::conf::TruncateConfig;
+use crate::erase::overwrite::{overwrite_regions, OverwriteBuffer};
+use crate::erase::remove::truncate_handle;
+use crate::erase::report::FileReport;
+use crate::util::errors::add_err;
+use crate::util::errors::wrap_io;
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Shrink the file to `config.length`, overwriting the part that is cut off first, including
+/// any space preallocated beyond the end. Files that are not longer than that are left alone.
+pub fn shred_tail(config: &TruncateConfig) -> ShredResult<FileReport> {
+    let verbose = config.verbosity.debug();
+    let path = &config.file;
+    let mut report = FileReport::new(path);
+    let mut file = match OpenOptions::new().write(true).open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            return Err(add_err(
+                format!("could not open '{}' for writing", escape_path(path)),
+                verbose,
+                err,
+            ))
+        }
+    };
+    let meta = wrap_io(|| "could not inspect file", file.metadata())?;
+    if !meta.is_file() {
+        return Err(format!("'{}' is not a file", escape_path(path)));
+    }
+    let old_len = meta.len();
+    if old_len <= config.length {
+        report.note(format!(
+            "file is {} bytes, so there is nothing to cut off",
+            old_len
+        ));
+        return Ok(report);
+    }
+
+    let beyond_eof_bytes = overwrite_regions(
+        &mut file,
+        &meta,
+        vec![(config.length, old_len - config.length)],
+        config.overwrite_count,
+        &mut OverwriteBuffer::new(),
+        verbose,
+    )?;
+    truncate_handle(&file, config.length, verbose)?;
+    wrap_io(|| "could not persist truncated file", file.sync_all())?;
+
+    report.note(format!(
+        "overwrote the last {} bytes {} times",
+        old_len - config.length,
+        config.overwrite_count
+    ));
+    if beyond_eof_bytes > 0 {
+        report.note(format!(
+            "also overwrote {} bytes preallocated beyond the end of the file",
+            beyond_eof_bytes
+        ));
+    }
+    report.note(format!("truncated to {} bytes", config.length));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs;
+
+    use ::tempfile::tempdir;
+
+    use crate::config::typ::Verbosity;
+
+    use super::*;
+
+    #[test]
+    fn cut_off_tail() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        fs::write(&path, b"keep this|secret tail").unwrap();
+        let config = TruncateConfig::non_interactive(path.clone(), 9, Verbosity::Debug, 2);
+        let report = shred_tail(&config).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"keep this");
+        assert!(report.notes[0].contains("last 12 bytes 2 times"));
+    }
+
+    #[test]
+    fn short_file_unchanged() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("log.txt");
+        fs::write(&path, b"short").unwrap();
+        let config = TruncateConfig::non_interactive(path.clone(), 100, Verbosity::Debug, 1);
+        let report = shred_tail(&config).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"short");
+        assert!(report.notes[0].contains("nothing to cut off"));
+    }
+}
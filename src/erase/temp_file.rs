@@ -0,0 +1,311 @@
+use ::std::fs;
+use ::std::fs::File;
+use ::std::fs::OpenOptions;
+use ::std::io;
+use ::std::io::Read;
+use ::std::io::Seek;
+use ::std::io::SeekFrom;
+use ::std::io::Write;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::config::conf::ShredConfig;
+use crate::config::typ::Verbosity;
+use crate::erase::handle::{remove_shredded, shred_handle};
+use crate::erase::remove::sync_parent_dir;
+use crate::util::errors::add_err;
+use crate::util::errors::wrap_io;
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// A temporary file that is shredded when it is dropped, so that secrets written to it are
+/// not left behind on error paths. Use [ShreddedTempFile::close] to see whether shredding
+/// worked; errors while dropping are ignored.
+#[derive(Debug)]
+pub struct ShreddedTempFile {
+    file: Option<File>,
+    path: PathBuf,
+    config: ShredConfig<PathBuf>,
+}
+
+#[cfg(unix)]
+fn private_file(options: &mut OpenOptions) {
+    use ::std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+}
+
+#[cfg(not(unix))]
+fn private_file(_options: &mut OpenOptions) {}
+
+impl ShreddedTempFile {
+    /// Create a new file at the path, readable and writable only by the owner.
+    /// Fails if the path already exists.
+    pub fn create(path: impl AsRef<Path>) -> ShredResult<Self> {
+        let path = path.as_ref();
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create_new(true);
+        private_file(&mut options);
+        let file = wrap_io(
+            || format!("could not create temporary file '{}'", escape_path(path)),
+            options.open(path),
+        )?;
+        Ok(ShreddedTempFile::from_parts(file, path.to_owned()))
+    }
+
+    /// Create a file with a random name in the default temporary directory.
+    #[cfg(feature = "tempfile")]
+    pub fn new() -> ShredResult<Self> {
+        ShreddedTempFile::from_named(wrap_io(
+            || "could not create temporary file",
+            ::tempfile::NamedTempFile::new(),
+        )?)
+    }
+
+    /// Create a file with a random name in the directory.
+    #[cfg(feature = "tempfile")]
+    pub fn new_in(dir: impl AsRef<Path>) -> ShredResult<Self> {
+        let dir = dir.as_ref();
+        ShreddedTempFile::from_named(wrap_io(
+            || format!("could not create temporary file in '{}'", escape_path(dir)),
+            ::tempfile::NamedTempFile::new_in(dir),
+        )?)
+    }
+
+    /// Take over a file from `tempfile`, which will then be shredded instead of just removed.
+    #[cfg(feature = "tempfile")]
+    pub fn from_named(file: ::tempfile::NamedTempFile) -> ShredResult<Self> {
+        match file.keep() {
+            Ok((file, path)) => Ok(ShreddedTempFile::from_parts(file, path)),
+            Err(err) => {
+                // The file is removed when it is dropped, so overwrite it first.
+                let mut file = err.file;
+                let shredded = shred_handle(file.as_file_mut(), &default_config());
+                let message = "could not take over temporary file";
+                match shredded {
+                    Ok(_) => Err(add_err(message, false, err.error)),
+                    Err(shred_err) => Err(format!(
+                        "{}, and it could not be shredded either: {}",
+                        add_err(message, false, err.error),
+                        shred_err
+                    )),
+                }
+            }
+        }
+    }
+
+    fn from_parts(file: File, path: PathBuf) -> Self {
+        ShreddedTempFile {
+            file: Some(file),
+            path,
+            config: default_config(),
+        }
+    }
+
+    /// Use these options for shredding, instead of the defaults. The files are ignored,
+    /// and `keep_files` is not allowed, as it would leave the file behind.
+    pub fn with_config<P: AsRef<Path>>(mut self, config: &ShredConfig<P>) -> ShredResult<Self> {
+        config.validate()?;
+        if config.keep_files {
+            return Err("temporary files cannot be kept after shredding".to_owned());
+        }
+        self.config = config.options();
+        self.config.confirmation_prompt = false;
+        self.config.progress_bar = false;
+        Ok(self)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn as_file(&self) -> &File {
+        self.file.as_ref().expect("file is only taken when closing")
+    }
+
+    pub fn as_file_mut(&mut self) -> &mut File {
+        self.file.as_mut().expect("file is only taken when closing")
+    }
+
+    /// Shred and remove the file now, and report any problem.
+    pub fn close(mut self) -> ShredResult<()> {
+        self.shred()
+    }
+
+    /// Move the file over the target instead of shredding it, replacing the target atomically.
+    /// The file is persisted first, so the target is never replaced by incomplete data.
+    /// If this fails, the file is still shredded on drop.
+    pub(crate) fn persist(mut self, target: &Path) -> ShredResult<()> {
+        let verbose = self.config.verbosity.debug();
+        self.sync(verbose)?;
+        if let Err(err) = fs::rename(&self.path, target) {
+            return Err(add_err(
+                format!("could not move temporary file to '{}'", escape_path(target)),
+                verbose,
+                err,
+            ));
+        }
+        self.file = None;
+        sync_parent_dir(target, verbose)
+    }
+
+    /// Like [ShreddedTempFile::persist], but fails if the target exists, without a moment in which
+    /// it could appear unnoticed: the target is added as a second name, then the own name removed.
+    pub(crate) fn persist_new(mut self, target: &Path) -> ShredResult<()> {
+        let verbose = self.config.verbosity.debug();
+        self.sync(verbose)?;
+        if let Err(err) = fs::hard_link(&self.path, target) {
+            if err.kind() == io::ErrorKind::AlreadyExists {
+                return Err(format!(
+                    "'{}' already exists, so it will not be replaced",
+                    escape_path(target)
+                ));
+            }
+            return Err(add_err(
+                format!("could not link temporary file to '{}'", escape_path(target)),
+                verbose,
+                err,
+            ));
+        }
+        // The data belongs to the target now, so the temporary name is removed without shredding.
+        self.file = None;
+        if let Err(err) = fs::remove_file(&self.path) {
+            return Err(add_err(
+                format!(
+                    "could not remove temporary file '{}'",
+                    escape_path(&self.path)
+                ),
+                verbose,
+                err,
+            ));
+        }
+        sync_parent_dir(target, verbose)
+    }
+
+    fn sync(&self, verbose: bool) -> ShredResult<()> {
+        match self.as_file().sync_all() {
+            Ok(()) => Ok(()),
+            Err(err) => Err(add_err("could not persist temporary file", verbose, err)),
+        }
+    }
+
+    /// Overwrite through the handle, so that nothing else can be put at the path in the meantime,
+    /// then rename and remove the file.
+    fn shred(&mut self) -> ShredResult<()> {
+        let Some(mut file) = self.file.take() else {
+            return Ok(());
+        };
+        shred_handle(&mut file, &self.config)?;
+        remove_shredded(&self.path, file, &self.config)
+    }
+}
+
+fn default_config() -> ShredConfig<PathBuf> {
+    ShredConfig::non_interactive(vec![], Verbosity::Quiet, false, 10, 10)
+}
+
+impl Drop for ShreddedTempFile {
+    fn drop(&mut self) {
+        let _ = self.shred();
+    }
+}
+
+impl Read for ShreddedTempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_file_mut().read(buf)
+    }
+}
+
+impl Write for ShreddedTempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.as_file_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.as_file_mut().flush()
+    }
+}
+
+impl Seek for ShreddedTempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.as_file_mut().seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn close_removes_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.tmp");
+        let mut file = ShreddedTempFile::create(&path).unwrap();
+        file.write_all(b"decrypted").unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut data = String::new();
+        file.read_to_string(&mut data).unwrap();
+        assert_eq!(data, "decrypted");
+        assert!(path.exists());
+        file.close().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn drop_removes_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.tmp");
+        {
+            let mut file = ShreddedTempFile::create(&path).unwrap();
+            file.write_all(b"decrypted").unwrap();
+        }
+        assert!(!path.exists());
+        assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn refuse_existing() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.tmp");
+        ::std::fs::write(&path, b"data").unwrap();
+        assert!(ShreddedTempFile::create(&path).is_err());
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn refuse_keep() {
+        let dir = tempdir().unwrap();
+        let file = ShreddedTempFile::create(dir.path().join("secret.tmp")).unwrap();
+        let config = ShredConfig::<PathBuf>::builder().keep_files(true);
+        assert!(file.with_config(&config.build().unwrap()).is_err());
+        assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leave_replaced_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.tmp");
+        let mut file = ShreddedTempFile::create(&path).unwrap();
+        file.write_all(b"decrypted").unwrap();
+        let other = dir.path().join("other.txt");
+        fs::write(&other, b"unrelated").unwrap();
+        fs::rename(&other, &path).unwrap();
+        let err = file.close().unwrap_err();
+        assert!(err.contains("no longer the shredded file"));
+        assert_eq!(fs::read(&path).unwrap(), b"unrelated");
+    }
+
+    #[cfg(feature = "tempfile")]
+    #[test]
+    fn from_tempfile() {
+        let dir = tempdir().unwrap();
+        let mut file = ShreddedTempFile::new_in(dir.path()).unwrap();
+        file.write_all(b"decrypted").unwrap();
+        let path = file.path().to_owned();
+        assert!(path.exists());
+        file.close().unwrap();
+        assert!(!path.exists());
+    }
+}
@@ -1,4 +1,12 @@
-use crate::{ShredResult, Verbosity};
+use crate::config::conf::ShredConfig;
+use crate::config::typ::SparseMode;
+use crate::inspect::flags::{read_flags, FileFlags};
+use crate::inspect::scheme::{choose_scheme, SchemeChoice};
+use crate::inspect::sparse::allocated_size;
+use crate::inspect::storage::{detect_storage, StorageKind};
+use crate::util::escape::escape_path;
+use crate::util::privilege::is_root;
+use crate::ShredResult;
 use std::path::Path;
 use std::{fmt, fs};
 
@@ -6,48 +14,123 @@ use std::{fmt, fs};
 pub struct FileInfo<'a> {
     pub path: &'a Path,
     pub size_kb: u64,
+    pub scheme: SchemeChoice,
+    pub storage: StorageKind,
+    /// Immutable or append-only flags, which have to be cleared before shredding (only with `force`).
+    pub flags: FileFlags,
 }
 
 impl<'a> fmt::Display for &'a FileInfo<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{} ({} kb)", self.path.to_string_lossy(), self.size_kb)
+        write!(
+            f,
+            "{} ({} kb; {}{})",
+            escape_path(self.path),
+            self.size_kb,
+            self.scheme,
+            if self.flags.is_protected() {
+                format!("; {}, will be cleared", self.flags)
+            } else {
+                String::new()
+            }
+        )
+    }
+}
+
+/// Why a file cannot be shredded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileProblem {
+    NotFound(String),
+    Protected(String),
+}
+
+impl fmt::Display for FileProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FileProblem::NotFound(msg) => f.write_str(msg),
+            FileProblem::Protected(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// Check that the file can be shredded, and decide how.
+pub fn inspect_file<'a, P: AsRef<Path>>(
+    file: &'a Path,
+    config: &ShredConfig<P>,
+) -> Result<FileInfo<'a>, FileProblem> {
+    let meta = match fs::metadata(file) {
+        Ok(meta) => meta,
+        Err(err) => {
+            return Err(FileProblem::NotFound(if config.verbosity.debug() {
+                format!(
+                    "could not read file '{}'; reason: {}",
+                    escape_path(file),
+                    err
+                )
+            } else {
+                format!("could not read file '{}'", escape_path(file))
+            }))
+        }
+    };
+    if !meta.is_file() {
+        return Err(FileProblem::NotFound(format!(
+            "path '{}' is not a file",
+            escape_path(file)
+        )));
+    }
+
+    // Flags are not available on all filesystems, in which case they cannot be set either.
+    let flags = read_flags(file).unwrap_or_default();
+    if flags.is_protected() {
+        if !config.force {
+            return Err(FileProblem::Protected(format!(
+                "file '{}' is {} (see chattr), so it cannot be overwritten; use --force as root to clear this",
+                escape_path(file),
+                flags
+            )));
+        }
+        if !is_root() {
+            return Err(FileProblem::Protected(format!(
+                "file '{}' is {} (see chattr), and clearing that needs root",
+                escape_path(file),
+                flags
+            )));
+        }
     }
+
+    let storage = detect_storage(file);
+    Ok(FileInfo {
+        path: file,
+        size_kb: match config.sparse {
+            SparseMode::Densify => meta.len(),
+            SparseMode::DataOnly => allocated_size(&meta),
+        }
+        .div_ceil(1024),
+        scheme: choose_scheme(config.scheme, storage, config.overwrite_count),
+        storage,
+        flags,
+    })
 }
 
-pub fn collect_file_info<'a>(
+/// Inspect all files, and fail if any of them cannot be shredded, after reporting all problems.
+pub fn collect_file_info<'a, P: AsRef<Path>>(
     files: &'a [&'a Path],
-    verbosity: Verbosity,
+    config: &ShredConfig<P>,
 ) -> ShredResult<Vec<FileInfo<'a>>> {
     let mut infos = Vec::with_capacity(files.len());
     let mut not_found_cnt: u32 = 0;
+    let mut protected_cnt: u32 = 0;
     for file in files.iter() {
-        // Input file
-        let meta = match fs::metadata(file) {
-            Ok(meta) => meta,
-            Err(err) => {
-                if verbosity.debug() {
-                    eprintln!(
-                        "could not read file '{}'; reason: {}",
-                        &file.to_string_lossy(),
-                        err
-                    )
-                } else {
-                    eprintln!("could not read file '{}'", &file.to_string_lossy())
+        match inspect_file(file, config) {
+            Ok(info) => infos.push(info),
+            Err(problem) => {
+                eprintln!("{}", problem);
+                match problem {
+                    FileProblem::NotFound(_) => not_found_cnt += 1,
+                    FileProblem::Protected(_) => protected_cnt += 1,
                 }
-                not_found_cnt += 1;
-                continue;
             }
-        };
-        if !meta.is_file() {
-            eprintln!("path '{}' is not a file", &file.to_string_lossy());
-            not_found_cnt += 1;
-            continue;
         }
-
-        infos.push(FileInfo {
-            path: file,
-            size_kb: (meta.len() + 1023) / 1024,
-        });
     }
     if not_found_cnt > 0 {
         return Err(format!(
@@ -56,5 +139,35 @@ pub fn collect_file_info<'a>(
             if not_found_cnt > 1 { "s were" } else { " was" }
         ));
     }
+    if protected_cnt > 0 {
+        return Err(format!(
+            "aborting because {} input file{} immutable or append-only",
+            protected_cnt,
+            if protected_cnt > 1 { "s are" } else { " is" }
+        ));
+    }
     Ok(infos)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Scheme;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn display_non_utf8_name() {
+        use ::std::ffi::OsStr;
+        use ::std::os::unix::ffi::OsStrExt;
+
+        let info = FileInfo {
+            path: Path::new(OsStr::from_bytes(b"/tmp/caf\xe9.txt")),
+            size_kb: 1,
+            scheme: choose_scheme(Scheme::Fixed, StorageKind::Unknown, 1),
+            storage: StorageKind::Unknown,
+            flags: FileFlags::default(),
+        };
+        assert!((&info).to_string().starts_with("/tmp/caf\\xE9.txt (1 kb; "));
+    }
+}
@@ -0,0 +1,203 @@
+use ::std::fmt;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// Block device details, as read from sysfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub model: String,
+    pub size: u64,
+}
+
+impl fmt::Display for DeviceInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, model: {}, size: {} bytes = {:.1} GB)",
+            escape_path(&self.path),
+            self.name,
+            self.model,
+            self.size,
+            self.size as f64 / 1e9
+        )
+    }
+}
+
+/// Device numbers (major, minor) of all mounted filesystems, from the contents of `/proc/self/mountinfo`.
+pub fn mounted_device_numbers(mountinfo: &str) -> Vec<(u64, u64)> {
+    mountinfo
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(2))
+        .filter_map(parse_device_numbers)
+        .collect()
+}
+
+/// Parse "major:minor" as used in `/proc` and `/sys`.
+pub fn parse_device_numbers(text: &str) -> Option<(u64, u64)> {
+    let (major, minor) = text.trim().split_once(':')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::fs;
+    use ::std::os::unix::fs::FileTypeExt;
+    use ::std::os::unix::fs::MetadataExt;
+    use ::std::path::Path;
+
+    use crate::inspect::device::{mounted_device_numbers, parse_device_numbers, DeviceInfo};
+    use crate::inspect::storage::device_numbers;
+    use crate::util::errors::add_err;
+    use crate::util::escape::escape_path;
+    use crate::util::ShredResult;
+
+    fn read_sys(path: &Path) -> Option<String> {
+        fs::read_to_string(path)
+            .ok()
+            .map(|text| text.trim().to_owned())
+    }
+
+    pub fn inspect_device(path: &Path, verbose: bool) -> ShredResult<DeviceInfo> {
+        let meta = match fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(err) => {
+                return Err(add_err(
+                    format!("could not read device '{}'", escape_path(path)),
+                    verbose,
+                    err,
+                ))
+            }
+        };
+        if !meta.file_type().is_block_device() {
+            return Err(format!(
+                "path '{}' is not a block device",
+                escape_path(path)
+            ));
+        }
+        let numbers = device_numbers(meta.rdev());
+        let sys_dir = Path::new("/sys/dev/block").join(format!("{}:{}", numbers.0, numbers.1));
+        let name = match fs::canonicalize(&sys_dir) {
+            Ok(target) => target
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            Err(err) => {
+                return Err(add_err(
+                    format!(
+                        "could not find device '{}' in /sys/block",
+                        escape_path(path)
+                    ),
+                    verbose,
+                    err,
+                ))
+            }
+        };
+        // Partitions do not have a model, but their parent disk does.
+        let model = read_sys(&sys_dir.join("device/model"))
+            .or_else(|| read_sys(&sys_dir.join("../device/model")))
+            .filter(|model| !model.is_empty())
+            .unwrap_or_else(|| "unknown".to_owned());
+        let size = match read_sys(&sys_dir.join("size")).and_then(|s| s.parse::<u64>().ok()) {
+            Some(sectors) => sectors * 512,
+            None => {
+                return Err(format!(
+                    "could not read size of device '{}'",
+                    escape_path(path)
+                ))
+            }
+        };
+
+        // Refuse if the device, or any partition on it, is mounted or used by another device.
+        let mut in_use = vec![numbers];
+        if let Ok(entries) = fs::read_dir(&sys_dir) {
+            for entry in entries.flatten() {
+                if entry.path().join("partition").exists() {
+                    if let Some(part) =
+                        read_sys(&entry.path().join("dev")).and_then(|d| parse_device_numbers(&d))
+                    {
+                        in_use.push(part);
+                    }
+                }
+            }
+        }
+        let mountinfo = read_sys(Path::new("/proc/self/mountinfo")).unwrap_or_default();
+        if mounted_device_numbers(&mountinfo)
+            .iter()
+            .any(|mounted| in_use.contains(mounted))
+        {
+            return Err(format!(
+                "refusing to shred device '{}' because it (or a partition on it) is mounted",
+                escape_path(path)
+            ));
+        }
+        if fs::read_dir(sys_dir.join("holders"))
+            .map(|mut holders| holders.next().is_some())
+            .unwrap_or(false)
+        {
+            return Err(format!(
+                "refusing to shred device '{}' because it is used by another device (e.g. RAID, LVM or encryption)",
+                escape_path(path)
+            ));
+        }
+
+        Ok(DeviceInfo {
+            path: path.to_owned(),
+            name,
+            model,
+            size,
+        })
+    }
+}
+
+/// Read the model and size of a block device, and fail if it is not safe to shred it
+/// (it is not a block device, or it is mounted or otherwise in use).
+#[cfg(target_os = "linux")]
+pub fn inspect_device(path: &Path, verbose: bool) -> ShredResult<DeviceInfo> {
+    linux::inspect_device(path, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn inspect_device(path: &Path, _verbose: bool) -> ShredResult<DeviceInfo> {
+    Err(format!(
+        "cannot shred device '{}' because device mode is only supported on Linux",
+        escape_path(path)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn parse_mountinfo() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+25 22 0:21 / /proc rw,nosuid shared:12 - proc proc rw
+31 22 259:3 / /home rw,relatime shared:5 - ext4 /dev/nvme0n1p3 rw
+";
+        assert_eq!(
+            mounted_device_numbers(mountinfo),
+            vec![(8, 1), (0, 21), (259, 3)]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_numbers() {
+        assert_eq!(parse_device_numbers("7:0\n"), Some((7, 0)));
+        assert_eq!(parse_device_numbers("7"), None);
+        assert_eq!(parse_device_numbers("a:b"), None);
+    }
+
+    #[test]
+    fn regular_file_is_refused() {
+        let file = NamedTempFile::new().unwrap();
+        assert!(inspect_device(file.path(), false).is_err());
+    }
+}
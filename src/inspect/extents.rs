@@ -0,0 +1,141 @@
+use ::std::fs::File;
+
+use crate::util::ShredResult;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::fs::File;
+    use ::std::io;
+    use ::std::os::unix::io::AsRawFd;
+
+    use crate::util::errors::add_err;
+    use crate::util::ShredResult;
+
+    /// `_IOWR('f', 11, struct fiemap)`
+    const FS_IOC_FIEMAP: u32 = 0xc020_660b;
+    const FIEMAP_FLAG_SYNC: u32 = 0x1;
+    const FIEMAP_EXTENT_LAST: u32 = 0x1;
+    const EXTENTS_PER_CALL: usize = 64;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct FiemapExtent {
+        fe_logical: u64,
+        fe_physical: u64,
+        fe_length: u64,
+        fe_reserved64: [u64; 2],
+        fe_flags: u32,
+        fe_reserved: [u32; 3],
+    }
+
+    #[repr(C)]
+    struct Fiemap {
+        fm_start: u64,
+        fm_length: u64,
+        fm_flags: u32,
+        fm_mapped_extents: u32,
+        fm_extent_count: u32,
+        fm_reserved: u32,
+        fm_extents: [FiemapExtent; EXTENTS_PER_CALL],
+    }
+
+    pub fn allocated_end(file: &File, verbose: bool) -> ShredResult<Option<u64>> {
+        let mut end = 0;
+        let mut start = 0;
+        loop {
+            let mut map = Fiemap {
+                fm_start: start,
+                fm_length: u64::MAX - start,
+                fm_flags: FIEMAP_FLAG_SYNC,
+                fm_mapped_extents: 0,
+                fm_extent_count: EXTENTS_PER_CALL as u32,
+                fm_reserved: 0,
+                fm_extents: [FiemapExtent::default(); EXTENTS_PER_CALL],
+            };
+            let res = unsafe {
+                libc::ioctl(
+                    file.as_raw_fd(),
+                    FS_IOC_FIEMAP as libc::Ioctl,
+                    &mut map as *mut Fiemap,
+                )
+            };
+            if res != 0 {
+                let err = io::Error::last_os_error();
+                return match err.raw_os_error() {
+                    Some(libc::EOPNOTSUPP) | Some(libc::ENOTTY) | Some(libc::EINVAL) => Ok(None),
+                    _ => Err(add_err("could not read the extents of file", verbose, err)),
+                };
+            }
+            let extents = &map.fm_extents[..map.fm_mapped_extents as usize];
+            for extent in extents {
+                end = end.max(extent.fe_logical + extent.fe_length);
+            }
+            match extents.last() {
+                Some(last) if last.fe_flags & FIEMAP_EXTENT_LAST == 0 => {
+                    start = last.fe_logical + last.fe_length;
+                }
+                _ => return Ok(Some(end)),
+            }
+        }
+    }
+}
+
+/// End of the last extent allocated to the file, which can be beyond the file length
+/// if space was preallocated (e.g. `fallocate` with `FALLOC_FL_KEEP_SIZE`).
+/// Returns `None` if the filesystem cannot report extents.
+#[cfg(target_os = "linux")]
+pub fn allocated_end(file: &File, verbose: bool) -> ShredResult<Option<u64>> {
+    linux::allocated_end(file, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allocated_end(_file: &File, _verbose: bool) -> ShredResult<Option<u64>> {
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs::OpenOptions;
+    use ::std::io::Write;
+
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn end_of_plain_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plain.file");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&[b'x'; 10_000]).unwrap();
+        if let Some(end) = allocated_end(&file, true).unwrap() {
+            assert!(end >= 10_000);
+            assert!(end < 10_000 + 64 * 1024);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn end_of_preallocated_file() {
+        use ::std::os::unix::io::AsRawFd;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("prealloc.file");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[b'x'; 1000]).unwrap();
+        let res =
+            unsafe { libc::fallocate(file.as_raw_fd(), libc::FALLOC_FL_KEEP_SIZE, 0, 1024 * 1024) };
+        if res != 0 {
+            return; // filesystem does not support preallocation
+        }
+        assert_eq!(file.metadata().unwrap().len(), 1000);
+        if let Some(end) = allocated_end(&file, true).unwrap() {
+            assert!(end >= 1024 * 1024);
+        }
+    }
+}
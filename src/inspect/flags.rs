@@ -0,0 +1,320 @@
+use ::std::fmt;
+use ::std::io;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+
+/// Inode flags (as shown by `lsattr`) that matter for shredding.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileFlags {
+    /// The file cannot be changed, renamed or removed (`chattr +i`).
+    pub immutable: bool,
+    /// The file can only be appended to (`chattr +a`).
+    pub append_only: bool,
+}
+
+impl FileFlags {
+    /// Whether the flags prevent overwriting or removing the file.
+    pub fn is_protected(&self) -> bool {
+        self.immutable || self.append_only
+    }
+}
+
+impl fmt::Display for FileFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match (self.immutable, self.append_only) {
+            (true, true) => "immutable and append-only",
+            (true, false) => "immutable",
+            (false, true) => "append-only",
+            (false, false) => "unprotected",
+        })
+    }
+}
+
+/// Flags that can be set to help shredding. The no-copy-on-write flag (`chattr +C`) is not
+/// among them, since it only takes effect on empty files and so cannot help with existing data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpfulFlag {
+    /// Ask the filesystem to erase the data on removal (`chattr +s`), where it is implemented.
+    SecureDeletion,
+}
+
+impl fmt::Display for HelpfulFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HelpfulFlag::SecureDeletion => "secure deletion",
+        })
+    }
+}
+
+/// Why inode flags could not be read or changed.
+#[derive(Debug)]
+pub enum FlagsError {
+    /// The filesystem or platform does not have inode flags.
+    Unsupported,
+    /// Changing the flags needs more privileges (root, or `CAP_LINUX_IMMUTABLE`).
+    NotPermitted,
+    Io(io::Error),
+}
+
+impl fmt::Display for FlagsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagsError::Unsupported => f.write_str("not supported by the filesystem"),
+            FlagsError::NotPermitted => f.write_str("not permitted, this needs root"),
+            FlagsError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<io::Error> for FlagsError {
+    fn from(err: io::Error) -> Self {
+        #[cfg(unix)]
+        match err.raw_os_error() {
+            Some(libc::ENOTTY) | Some(libc::EOPNOTSUPP) | Some(libc::EINVAL) => {
+                return FlagsError::Unsupported
+            }
+            Some(libc::EPERM) | Some(libc::EACCES) => return FlagsError::NotPermitted,
+            _ => {}
+        }
+        FlagsError::Io(err)
+    }
+}
+
+/// Protection flags removed by [clear_protection], which are set again when this is dropped,
+/// so that a file keeps its protection when shredding fails halfway.
+#[derive(Debug)]
+pub struct ProtectionGuard {
+    path: PathBuf,
+    original: Option<FileFlags>,
+}
+
+impl ProtectionGuard {
+    /// Put back the original flags now.
+    pub fn restore(mut self) -> Result<(), FlagsError> {
+        match self.original.take() {
+            Some(flags) => restore_protection(&self.path, flags),
+            None => Ok(()),
+        }
+    }
+
+    /// Leave the flags cleared, e.g. because the file is about to be removed.
+    pub fn dismiss(mut self) {
+        self.original = None;
+    }
+}
+
+impl Drop for ProtectionGuard {
+    fn drop(&mut self) {
+        if let Some(flags) = self.original.take() {
+            let _ = restore_protection(&self.path, flags);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::fs::File;
+    use ::std::fs::OpenOptions;
+    use ::std::io;
+    use ::std::os::unix::fs::OpenOptionsExt;
+    use ::std::os::unix::io::AsRawFd;
+    use ::std::path::Path;
+
+    use super::{FileFlags, FlagsError, HelpfulFlag};
+
+    /// `_IOR('f', 1, long)`
+    const FS_IOC_GETFLAGS: u32 = 0x8000_6601 | ((size_of::<libc::c_long>() as u32) << 16);
+    /// `_IOW('f', 2, long)`
+    const FS_IOC_SETFLAGS: u32 = 0x4000_6602 | ((size_of::<libc::c_long>() as u32) << 16);
+    const FS_SECRM_FL: libc::c_int = 0x0000_0001;
+    const FS_IMMUTABLE_FL: libc::c_int = 0x0000_0010;
+    const FS_APPEND_FL: libc::c_int = 0x0000_0020;
+
+    /// Open without write access, which works also for immutable files (like `chattr`).
+    fn open(path: &Path) -> Result<File, FlagsError> {
+        Ok(OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_NOFOLLOW | libc::O_NOCTTY)
+            .open(path)?)
+    }
+
+    fn get_flags(file: &File) -> Result<libc::c_int, FlagsError> {
+        let mut flags: libc::c_int = 0;
+        let res = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                FS_IOC_GETFLAGS as libc::Ioctl,
+                &mut flags as *mut libc::c_int,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(flags)
+    }
+
+    fn set_flags(file: &File, flags: libc::c_int) -> Result<(), FlagsError> {
+        let res = unsafe {
+            libc::ioctl(
+                file.as_raw_fd(),
+                FS_IOC_SETFLAGS as libc::Ioctl,
+                &flags as *const libc::c_int,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        Ok(())
+    }
+
+    pub fn read_flags(path: &Path) -> Result<FileFlags, FlagsError> {
+        let flags = get_flags(&open(path)?)?;
+        Ok(FileFlags {
+            immutable: flags & FS_IMMUTABLE_FL != 0,
+            append_only: flags & FS_APPEND_FL != 0,
+        })
+    }
+
+    pub fn clear_protection(path: &Path) -> Result<(), FlagsError> {
+        let file = open(path)?;
+        let flags = get_flags(&file)?;
+        set_flags(&file, flags & !(FS_IMMUTABLE_FL | FS_APPEND_FL))
+    }
+
+    pub fn restore_protection(path: &Path, original: FileFlags) -> Result<(), FlagsError> {
+        let file = open(path)?;
+        let mut flags = get_flags(&file)?;
+        if original.immutable {
+            flags |= FS_IMMUTABLE_FL;
+        }
+        if original.append_only {
+            flags |= FS_APPEND_FL;
+        }
+        set_flags(&file, flags)
+    }
+
+    pub fn add_flag(path: &Path, flag: HelpfulFlag) -> Result<(), FlagsError> {
+        let file = open(path)?;
+        let flags = get_flags(&file)?;
+        let mask = match flag {
+            HelpfulFlag::SecureDeletion => FS_SECRM_FL,
+        };
+        set_flags(&file, flags | mask)?;
+        // Some filesystems silently ignore flags they do not implement.
+        if get_flags(&file)? & mask == 0 {
+            return Err(FlagsError::Unsupported);
+        }
+        Ok(())
+    }
+}
+
+/// Read the immutable and append-only flags of the file (like `lsattr`).
+#[cfg(target_os = "linux")]
+pub fn read_flags(path: &Path) -> Result<FileFlags, FlagsError> {
+    linux::read_flags(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn read_flags(_path: &Path) -> Result<FileFlags, FlagsError> {
+    Err(FlagsError::Unsupported)
+}
+
+/// Remove the immutable and append-only flags, which usually requires root. They are set
+/// again when the returned guard is dropped, unless it is dismissed.
+pub fn clear_protection(path: &Path, flags: FileFlags) -> Result<ProtectionGuard, FlagsError> {
+    clear_flags(path)?;
+    Ok(ProtectionGuard {
+        path: path.to_owned(),
+        original: Some(flags),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn clear_flags(path: &Path) -> Result<(), FlagsError> {
+    linux::clear_protection(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clear_flags(_path: &Path) -> Result<(), FlagsError> {
+    Err(FlagsError::Unsupported)
+}
+
+#[cfg(target_os = "linux")]
+fn restore_protection(path: &Path, flags: FileFlags) -> Result<(), FlagsError> {
+    linux::restore_protection(path, flags)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn restore_protection(_path: &Path, _flags: FileFlags) -> Result<(), FlagsError> {
+    Err(FlagsError::Unsupported)
+}
+
+/// Set a flag that helps shredding, failing with `Unsupported` if the filesystem ignores it.
+#[cfg(target_os = "linux")]
+pub fn add_flag(path: &Path, flag: HelpfulFlag) -> Result<(), FlagsError> {
+    linux::add_flag(path, flag)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn add_flag(_path: &Path, _flag: HelpfulFlag) -> Result<(), FlagsError> {
+    Err(FlagsError::Unsupported)
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs;
+
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn plain_file_is_unprotected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        match read_flags(&path) {
+            Ok(flags) => assert!(!flags.is_protected()),
+            Err(FlagsError::Unsupported) => {}
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("missing.txt");
+        assert!(
+            matches!(read_flags(&path), Err(FlagsError::Io(_))) || cfg!(not(target_os = "linux"))
+        );
+    }
+
+    #[test]
+    fn restore_protection_on_drop() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("file.txt");
+        fs::write(&path, b"data").unwrap();
+        let flags = FileFlags {
+            immutable: true,
+            append_only: false,
+        };
+        // Needs root and a filesystem with inode flags.
+        if restore_protection(&path, flags).is_err() {
+            return;
+        }
+        drop(clear_protection(&path, flags).unwrap());
+        assert_eq!(read_flags(&path).unwrap(), flags);
+        clear_protection(&path, flags).unwrap().dismiss();
+        assert!(!read_flags(&path).unwrap().is_protected());
+    }
+
+    #[test]
+    fn describe_flags() {
+        let flags = FileFlags {
+            immutable: true,
+            append_only: false,
+        };
+        assert!(flags.is_protected());
+        assert_eq!(flags.to_string(), "immutable");
+    }
+}
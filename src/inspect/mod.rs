@@ -1 +1,7 @@
 pub mod collect;
+pub mod device;
+pub mod extents;
+pub mod flags;
+pub mod scheme;
+pub mod sparse;
+pub mod storage;
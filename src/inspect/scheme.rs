@@ -0,0 +1,97 @@
+use ::std::fmt;
+
+use crate::config::typ::Scheme;
+use crate::inspect::storage::StorageKind;
+
+/// How a specific file will be overwritten, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemeChoice {
+    pub overwrite_count: u32,
+    pub discard: bool,
+    pub reason: String,
+}
+
+impl fmt::Display for SchemeChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} pass{}{}: {}",
+            self.overwrite_count,
+            if self.overwrite_count == 1 { "" } else { "es" },
+            if self.discard { " + discard" } else { "" },
+            self.reason
+        )
+    }
+}
+
+/// Decide the number of passes for a file, given the storage it is on.
+pub fn choose_scheme(scheme: Scheme, storage: StorageKind, overwrite_count: u32) -> SchemeChoice {
+    match (scheme, storage) {
+        (Scheme::Fixed, _) => SchemeChoice {
+            overwrite_count,
+            discard: false,
+            reason: "fixed scheme".to_owned(),
+        },
+        (Scheme::Auto, StorageKind::Rotational) => SchemeChoice {
+            overwrite_count,
+            discard: false,
+            reason: format!("{}, multiple passes reduce residual magnetization", storage),
+        },
+        (Scheme::Auto, StorageKind::SolidState) => SchemeChoice {
+            overwrite_count: 1,
+            discard: true,
+            reason: format!(
+                "{}, wear leveling makes extra passes useless, so one random pass and TRIM",
+                storage
+            ),
+        },
+        (Scheme::Auto, StorageKind::Tmpfs) => SchemeChoice {
+            overwrite_count: 1,
+            discard: false,
+            reason: format!("{}, one pass clears the memory pages", storage),
+        },
+        (Scheme::Auto, StorageKind::CopyOnWrite(_)) => SchemeChoice {
+            overwrite_count: 1,
+            discard: false,
+            reason: format!(
+                "WARNING: {}, overwriting writes new blocks and old data may remain in snapshots or free space",
+                storage
+            ),
+        },
+        (Scheme::Auto, StorageKind::Unknown) => SchemeChoice {
+            overwrite_count,
+            discard: false,
+            reason: format!("{}, using configured passes", storage),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_ignores_storage() {
+        let choice = choose_scheme(Scheme::Fixed, StorageKind::SolidState, 7);
+        assert_eq!(choice.overwrite_count, 7);
+        assert!(!choice.discard);
+    }
+
+    #[test]
+    fn auto_per_storage() {
+        assert_eq!(
+            choose_scheme(Scheme::Auto, StorageKind::Rotational, 7).overwrite_count,
+            7
+        );
+        let ssd = choose_scheme(Scheme::Auto, StorageKind::SolidState, 7);
+        assert_eq!(ssd.overwrite_count, 1);
+        assert!(ssd.discard);
+        assert_eq!(
+            choose_scheme(Scheme::Auto, StorageKind::Tmpfs, 7).overwrite_count,
+            1
+        );
+        let cow = choose_scheme(Scheme::Auto, StorageKind::CopyOnWrite("btrfs"), 7);
+        assert!(cow.reason.contains("WARNING"));
+        assert!(cow.reason.contains("btrfs"));
+    }
+}
@@ -0,0 +1,141 @@
+use ::std::fs::File;
+use ::std::fs::Metadata;
+
+use crate::util::ShredResult;
+
+/// Number of bytes that take up space on disk, which is less than the length for sparse files.
+#[cfg(unix)]
+pub fn allocated_size(meta: &Metadata) -> u64 {
+    use ::std::os::unix::fs::MetadataExt;
+    (meta.blocks() * 512).min(meta.len())
+}
+
+#[cfg(not(unix))]
+pub fn allocated_size(meta: &Metadata) -> u64 {
+    meta.len()
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::fs::File;
+    use ::std::io;
+    use ::std::os::unix::io::AsRawFd;
+
+    use crate::util::errors::add_err;
+    use crate::util::ShredResult;
+
+    /// Seek to the next data or hole from `offset`, or `None` if there is no more data.
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<Option<u64>> {
+        let res = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if res >= 0 {
+            return Ok(Some(res as u64));
+        }
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENXIO) {
+            return Ok(None);
+        }
+        Err(err)
+    }
+
+    pub fn data_regions(
+        file: &File,
+        file_size: u64,
+        verbose: bool,
+    ) -> ShredResult<Vec<(u64, u64)>> {
+        let mut regions = vec![];
+        let mut offset = 0;
+        while offset < file_size {
+            let start = match seek(file, offset, libc::SEEK_DATA) {
+                Ok(Some(start)) => start,
+                Ok(None) => break,
+                // Filesystem cannot tell where holes are, so treat everything as data.
+                Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                    return Ok(vec![(0, file_size)])
+                }
+                Err(err) => {
+                    return Err(add_err("could not find data in sparse file", verbose, err))
+                }
+            };
+            let end = match seek(file, start, libc::SEEK_HOLE) {
+                Ok(Some(end)) => end.min(file_size),
+                Ok(None) => file_size,
+                Err(err) => {
+                    return Err(add_err("could not find holes in sparse file", verbose, err))
+                }
+            };
+            if end > start {
+                regions.push((start, end - start));
+            }
+            offset = end;
+        }
+        Ok(regions)
+    }
+}
+
+/// Byte ranges (start, length) of the file that contain data, skipping the holes of sparse files.
+/// If the platform or filesystem cannot report holes, the whole file is one region.
+#[cfg(target_os = "linux")]
+pub fn data_regions(file: &File, file_size: u64, verbose: bool) -> ShredResult<Vec<(u64, u64)>> {
+    linux::data_regions(file, file_size, verbose)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn data_regions(_file: &File, file_size: u64, _verbose: bool) -> ShredResult<Vec<(u64, u64)>> {
+    if file_size == 0 {
+        return Ok(vec![]);
+    }
+    Ok(vec![(0, file_size)])
+}
+
+#[cfg(test)]
+mod tests {
+    use ::std::fs::OpenOptions;
+    use ::std::io::Seek;
+    use ::std::io::SeekFrom;
+    use ::std::io::Write;
+
+    use ::tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn regions_of_sparse_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sparse.img");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.write_all(&[b'x'; 4096]).unwrap();
+        file.seek(SeekFrom::Start(512 * 1024)).unwrap();
+        file.write_all(&[b'y'; 4096]).unwrap();
+        file.set_len(1024 * 1024).unwrap();
+        file.sync_all().unwrap();
+
+        let regions = data_regions(&file, 1024 * 1024, true).unwrap();
+        let covered = |pos: u64| {
+            regions
+                .iter()
+                .any(|(start, len)| *start <= pos && pos < start + len)
+        };
+        assert!(covered(0));
+        assert!(covered(512 * 1024));
+        let total: u64 = regions.iter().map(|(_, len)| len).sum();
+        assert!(total <= 1024 * 1024);
+        if allocated_size(&file.metadata().unwrap()) < 1024 * 1024 {
+            assert!(regions.len() >= 2);
+            assert!(!covered(256 * 1024));
+        }
+    }
+
+    #[test]
+    fn regions_of_empty_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("empty.file");
+        let file = File::create(&path).unwrap();
+        assert!(data_regions(&file, 0, true).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use ::std::fmt;
+use ::std::path::Path;
+
+use crate::util::escape::escape_path;
+use crate::util::ShredResult;
+
+/// The kind of storage a file lives on, as far as it matters for shredding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    Rotational,
+    SolidState,
+    Tmpfs,
+    CopyOnWrite(&'static str),
+    Unknown,
+}
+
+impl fmt::Display for StorageKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageKind::Rotational => f.write_str("rotational disk"),
+            StorageKind::SolidState => f.write_str("solid state drive"),
+            StorageKind::Tmpfs => f.write_str("in-memory filesystem"),
+            StorageKind::CopyOnWrite(name) => write!(f, "copy-on-write filesystem ({})", name),
+            StorageKind::Unknown => f.write_str("unknown storage"),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use ::std::ffi::CString;
+    use ::std::fs;
+    use ::std::mem::MaybeUninit;
+    use ::std::os::unix::ffi::OsStrExt;
+    use ::std::os::unix::fs::MetadataExt;
+    use ::std::path::Path;
+
+    use super::StorageKind;
+
+    const TMPFS_MAGIC: u64 = 0x0102_1994;
+    const RAMFS_MAGIC: u64 = 0x8584_58f6;
+    const BTRFS_MAGIC: u64 = 0x9123_683e;
+    const ZFS_MAGIC: u64 = 0x2fc1_2fc1;
+    const BCACHEFS_MAGIC: u64 = 0xca45_1a4e;
+
+    /// Filesystem magic number from `statfs`, if it could be read.
+    pub fn filesystem_type(path: &Path) -> Option<u64> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stat = MaybeUninit::<libc::statfs>::uninit();
+        let res = unsafe { libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+        if res != 0 {
+            return None;
+        }
+        let stat = unsafe { stat.assume_init() };
+        // The type of `f_type` differs per platform, and magic numbers can look negative.
+        Some(stat.f_type as u64 & 0xffff_ffff)
+    }
+
+    /// Split a device number into major and minor, using the glibc encoding.
+    pub fn device_numbers(dev: u64) -> (u64, u64) {
+        let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+        let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+        (major, minor)
+    }
+
+    /// Read the `rotational` flag of the block device, trying the parent disk for partitions.
+    fn is_rotational(path: &Path) -> Option<bool> {
+        let dev = fs::metadata(path).ok()?.dev();
+        let (major, minor) = device_numbers(dev);
+        let base = format!("/sys/dev/block/{}:{}", major, minor);
+        for candidate in ["queue/rotational", "../queue/rotational"] {
+            if let Ok(text) = fs::read_to_string(format!("{}/{}", base, candidate)) {
+                return Some(text.trim() == "1");
+            }
+        }
+        None
+    }
+
+    pub fn detect_storage(path: &Path) -> StorageKind {
+        match filesystem_type(path) {
+            Some(TMPFS_MAGIC) | Some(RAMFS_MAGIC) => return StorageKind::Tmpfs,
+            Some(BTRFS_MAGIC) => return StorageKind::CopyOnWrite("btrfs"),
+            Some(ZFS_MAGIC) => return StorageKind::CopyOnWrite("zfs"),
+            Some(BCACHEFS_MAGIC) => return StorageKind::CopyOnWrite("bcachefs"),
+            _ => {}
+        }
+        match is_rotational(path) {
+            Some(true) => StorageKind::Rotational,
+            Some(false) => StorageKind::SolidState,
+            None => StorageKind::Unknown,
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::device_numbers;
+
+/// Find out what kind of storage the file is on. Falls back to `Unknown` if this cannot be determined.
+#[cfg(target_os = "linux")]
+pub fn detect_storage(path: &Path) -> StorageKind {
+    linux::detect_storage(path)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn detect_storage(_path: &Path) -> StorageKind {
+    StorageKind::Unknown
+}
+
+/// Number of bytes that unprivileged users can still write on the filesystem containing the path.
+#[cfg(unix)]
+pub fn available_space(path: &Path, verbose: bool) -> ShredResult<u64> {
+    use ::std::ffi::CString;
+    use ::std::io;
+    use ::std::mem::MaybeUninit;
+    use ::std::os::unix::ffi::OsStrExt;
+
+    use crate::util::errors::add_err;
+
+    let c_path = match CString::new(path.as_os_str().as_bytes()) {
+        Ok(c_path) => c_path,
+        Err(err) => return Err(add_err("path contains a nul byte", verbose, err)),
+    };
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    let res = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if res != 0 {
+        return Err(add_err(
+            format!("could not determine free space for '{}'", escape_path(path)),
+            verbose,
+            io::Error::last_os_error(),
+        ));
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn available_space(path: &Path, _verbose: bool) -> ShredResult<u64> {
+    Err(format!(
+        "cannot determine free space for '{}' because this is only supported on Unix",
+        escape_path(path)
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use ::tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn detect_does_not_fail() {
+        let file = NamedTempFile::new().unwrap();
+        let kind = detect_storage(file.path());
+        assert!(!kind.to_string().is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn split_device_numbers() {
+        assert_eq!(device_numbers(0x0803), (8, 3));
+        assert_eq!(device_numbers(0x10_0300), (3, 256));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn some_space_available() {
+        let file = NamedTempFile::new().unwrap();
+        assert!(available_space(file.path(), true).unwrap() > 0);
+    }
+
+    #[test]
+    fn missing_file_is_unknown() {
+        let kind = detect_storage(Path::new("/this/path/does/not/exist.file"));
+        assert_eq!(kind, StorageKind::Unknown);
+    }
+}
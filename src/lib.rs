@@ -1,13 +1,42 @@
+use ::std::fs::File;
+use ::std::io;
 use ::std::path::Path;
 
 use ::indicatif::ProgressBar;
 
+pub use crate::config::conf::DeviceConfig;
+pub use crate::config::conf::FreeSpaceConfig;
 pub use crate::config::conf::ShredConfig;
+pub use crate::config::conf::ShredConfigBuilder;
+pub use crate::config::conf::TruncateConfig;
+pub use crate::config::typ::RenameStrategy;
+pub use crate::config::typ::Scheme;
+pub use crate::config::typ::ScratchDir;
+pub use crate::config::typ::SparseMode;
+pub use crate::config::typ::TimestampPolicy;
 pub use crate::config::typ::Verbosity;
-use crate::erase::orchestrate::delete_file;
+use crate::erase::device::erase_device;
+use crate::erase::dirents::wipe_directory_entries;
+use crate::erase::free_space::wipe_free_space;
+use crate::erase::handle::shred_handle;
+#[cfg(unix)]
+use crate::erase::handle::unlink_at;
+use crate::erase::replace::{replace_contents, transform_to};
+pub use crate::erase::report::FileReport;
+#[cfg(feature = "async")]
+use crate::erase::shredder::run_blocking;
+#[cfg(feature = "async")]
+pub use crate::erase::shredder::ShredEvent;
+pub use crate::erase::shredder::Shredder;
+use crate::erase::tail::shred_tail;
+pub use crate::erase::temp_file::ShreddedTempFile;
 use crate::inspect::collect::collect_file_info;
-use crate::util::cli::confirm_delete;
+use crate::inspect::device::inspect_device;
+use crate::inspect::storage::available_space;
+use crate::util::cli::{confirm_delete, confirm_device, confirm_free_space, confirm_truncate};
+use crate::util::errors::wrap_io;
 pub use crate::util::errors::ShredResult;
+pub use crate::util::escape::escape_path;
 
 mod config;
 mod erase;
@@ -15,30 +44,24 @@ mod inspect;
 mod util;
 
 pub fn shred<P: AsRef<Path>>(config: &ShredConfig<P>) -> ShredResult<()> {
+    config.validate()?;
     let files: Vec<_> = config.files.iter().map(|f| (*f).as_ref()).collect();
-    let files = collect_file_info(&files, config.verbosity)?;
-    let total_kb = files.iter().map(|f| f.size_kb).sum::<u64>() + 10_000;
-    let progress = if config.progress_bar {
-        Some(ProgressBar::new(total_kb))
-    } else {
-        None
-    };
+    let files = collect_file_info(&files, config)?;
     if config.confirmation_prompt {
         confirm_delete(&files, config.verbosity.debug())?;
     }
-    if let Some(ref pb) = progress {
-        pb.inc(10_000);
-    }
+    let total_kb = files.iter().map(|f| f.size_kb).sum();
+    let mut shredder = Shredder::new(config)?.with_total_size(total_kb);
+    let mut reports = Vec::with_capacity(files.len());
     for file in &files {
-        delete_file(file.path, config)?;
-        if let Some(ref pb) = progress {
-            pb.inc(file.size_kb);
-        }
-    }
-    if let Some(ref pb) = progress {
-        pb.finish_with_message("done");
+        reports.push(shredder.shred_info(file)?);
     }
+    let dirs = shredder.finish_dirs()?;
     if !config.verbosity.quiet() {
+        for report in &reports {
+            println!("{}", report);
+        }
+        dirs.print();
         if config.keep_files {
             println!("removed data from {} files", config.files.len());
         } else {
@@ -48,6 +71,209 @@ pub fn shred<P: AsRef<Path>>(config: &ShredConfig<P>) -> ShredResult<()> {
     Ok(())
 }
 
+/// Overwrite a file through a handle that is open for writing (not appending), and truncate it
+/// unless `keep_files` is set. Nothing is done by path, so the files of the config are ignored,
+/// and the file is not renamed or removed.
+pub fn shred_open_file<P: AsRef<Path>>(
+    file: &mut File,
+    config: &ShredConfig<P>,
+) -> ShredResult<()> {
+    shred_handle(file, config).map(|_| ())
+}
+
+/// Like [shred_open_file], for any descriptor that is open for writing.
+#[cfg(unix)]
+pub fn shred_fd<P: AsRef<Path>>(
+    fd: impl ::std::os::fd::AsFd,
+    config: &ShredConfig<P>,
+) -> ShredResult<()> {
+    let fd = wrap_io(
+        || "could not duplicate file descriptor",
+        fd.as_fd().try_clone_to_owned(),
+    )?;
+    shred_handle(&mut File::from(fd), config).map(|_| ())
+}
+
+/// Like [shred_fd], then remove `name` from the directory open as `dir`, unless `keep_files`
+/// is set. The name is only removed if it still refers to the shredded file.
+#[cfg(unix)]
+pub fn shred_fd_at<P: AsRef<Path>>(
+    fd: impl ::std::os::fd::AsFd,
+    dir: impl ::std::os::fd::AsFd,
+    name: &::std::ffi::OsStr,
+    config: &ShredConfig<P>,
+) -> ShredResult<()> {
+    let fd = wrap_io(
+        || "could not duplicate file descriptor",
+        fd.as_fd().try_clone_to_owned(),
+    )?;
+    let mut file = File::from(fd);
+    shred_handle(&mut file, config)?;
+    if config.keep_files {
+        return Ok(());
+    }
+    unlink_at(dir.as_fd(), name, &file, config.verbosity.debug())
+}
+
+/// Replace the contents of the file by what `new_contents` writes, and shred the old contents.
+/// The new version is moved into place atomically, and only then is the old data overwritten,
+/// through a handle opened beforehand. The files and `keep_files` of the config are not used.
+pub fn secure_replace<P: AsRef<Path>>(
+    path: &Path,
+    new_contents: impl FnOnce(&mut File) -> io::Result<()>,
+    config: &ShredConfig<P>,
+) -> ShredResult<FileReport> {
+    let report = replace_contents(path, new_contents, config)?;
+    if !config.verbosity.quiet() {
+        println!("{}", report);
+    }
+    Ok(report)
+}
+
+/// Write `target` using `transform`, which gets `source` to read from (e.g. to encrypt it),
+/// and then shred `source`. The target only appears once it is complete. The files and
+/// `keep_files` of the config are not used.
+pub fn secure_transform<P: AsRef<Path>>(
+    source: &Path,
+    target: &Path,
+    transform: impl FnOnce(&mut File, &mut File) -> io::Result<()>,
+    config: &ShredConfig<P>,
+) -> ShredResult<FileReport> {
+    let report = transform_to(source, target, transform, config)?;
+    if !config.verbosity.quiet() {
+        println!("{}", report);
+    }
+    Ok(report)
+}
+
+/// Like [shred], but without blocking the `tokio` runtime, for use in async services.
+/// The files are all checked first, then shredded one by one, and reports are returned instead
+/// of printed. Progress events are sent to `events` if given. Confirmation prompts are not
+/// supported. Like [Shredder::shred_all], a failed file does not stop the others; the
+/// directories are still finished, and then the errors of all failed files and of finishing
+/// are returned together.
+/// Dropping the future stops after the current phase, see [Shredder].
+#[cfg(feature = "async")]
+pub async fn shred_async<P: AsRef<Path>>(
+    config: &ShredConfig<P>,
+    events: Option<::tokio::sync::mpsc::UnboundedSender<ShredEvent>>,
+) -> ShredResult<Vec<FileReport>> {
+    if config.confirmation_prompt {
+        return Err("cannot ask for confirmation when shredding asynchronously".to_owned());
+    }
+    let mut shredder = Shredder::new(config)?;
+    if let Some(events) = events {
+        shredder = shredder.with_events(events);
+    }
+    let files: Vec<_> = config.files.iter().map(|f| f.as_ref().to_owned()).collect();
+    let options = config.options();
+    let files = run_blocking(move || {
+        let paths: Vec<_> = files.iter().map(|f| f.as_path()).collect();
+        collect_file_info(&paths, &options)?;
+        Ok(files)
+    })
+    .await?;
+    let mut reports = Vec::with_capacity(files.len());
+    let mut errors = vec![];
+    for file in &files {
+        match shredder.shred_path_async(file).await {
+            Ok(report) => reports.push(report),
+            Err(err) => errors.push(err),
+        }
+    }
+    // Finishing also fails on its own, which should not hide why files failed.
+    match shredder.finish_async().await {
+        Ok(dir_reports) => reports.extend(dir_reports),
+        Err(err) => errors.push(err),
+    }
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+    Ok(reports)
+}
+
+/// Overwrite (part of) a whole block device, and optionally ask the kernel to sanitize it.
+/// Refuses devices that are mounted or in use.
+pub fn shred_device(config: &DeviceConfig) -> ShredResult<FileReport> {
+    config.validate()?;
+    let verbose = config.verbosity.debug();
+    let info = inspect_device(&config.device, verbose)?;
+    if config.confirmation_prompt {
+        confirm_device(&info, verbose)?;
+    }
+    let progress = if config.progress_bar {
+        Some(ProgressBar::new(
+            config.overwrite_count as u64 + if config.sanitize { 1 } else { 0 },
+        ))
+    } else {
+        None
+    };
+    let report = erase_device(&info, config, progress.as_ref())?;
+    if let Some(ref pb) = progress {
+        pb.finish_with_message("done");
+    }
+    if !config.verbosity.quiet() {
+        println!("{}", report);
+    }
+    Ok(report)
+}
+
+/// Overwrite the free space of the filesystem that contains the directory, so that files
+/// deleted earlier (without shredding) cannot be recovered from it.
+pub fn shred_free_space(config: &FreeSpaceConfig) -> ShredResult<FileReport> {
+    config.validate()?;
+    let verbose = config.verbosity.debug();
+    if config.confirmation_prompt {
+        confirm_free_space(&config.dir, available_space(&config.dir, verbose)?, verbose)?;
+    }
+    let progress = if config.progress_bar {
+        Some(ProgressBar::new(0))
+    } else {
+        None
+    };
+    let report = wipe_free_space(config, progress.as_ref())?;
+    if let Some(ref pb) = progress {
+        pb.finish_with_message("done");
+    }
+    if !config.verbosity.quiet() {
+        println!("{}", report);
+    }
+    Ok(report)
+}
+
+/// Shrink the file to `config.length` bytes, overwriting the part that is cut off first,
+/// since truncating alone releases those blocks with the data still on them.
+pub fn shred_truncate_with(config: &TruncateConfig) -> ShredResult<FileReport> {
+    let verbose = config.verbosity.debug();
+    config.validate()?;
+    if config.confirmation_prompt {
+        let current = wrap_io(
+            || format!("could not read file '{}'", escape_path(&config.file)),
+            ::std::fs::metadata(&config.file),
+        )?
+        .len();
+        if current > config.length {
+            confirm_truncate(&config.file, current, config.length, verbose)?;
+        }
+    }
+    let report = shred_tail(config)?;
+    if !config.verbosity.quiet() {
+        println!("{}", report);
+    }
+    Ok(report)
+}
+
+/// Overwrite unused directory entries, which may still contain names of removed files.
+pub fn shred_directory_entries(dir: &Path, verbosity: Verbosity) -> ShredResult<FileReport> {
+    let count = wipe_directory_entries(dir, verbosity.debug())?;
+    let mut report = FileReport::new(dir);
+    report.note(format!("overwrote {} directory entries", count));
+    if !verbosity.quiet() {
+        println!("{}", report);
+    }
+    Ok(report)
+}
+
 /// Easy-use wrapper for `shred` that uses defaults for most options and shreds only one file.
 pub fn shred_file(path: &Path) -> ShredResult<()> {
     shred(&ShredConfig::non_interactive(
@@ -59,14 +285,28 @@ pub fn shred_file(path: &Path) -> ShredResult<()> {
     ))
 }
 
+/// Easy-use wrapper for `shred_truncate_with` that uses defaults for the other options.
+pub fn shred_truncate(path: &Path, new_len: u64) -> ShredResult<()> {
+    shred_truncate_with(&TruncateConfig::non_interactive(
+        path.to_owned(),  // file
+        new_len,          // length
+        Verbosity::Quiet, // verbosity
+        10,               // overwrite_count
+    ))
+    .map(|_| ())
+}
+
 #[cfg(test)]
 mod tests {
+    use ::std::fs;
     use ::std::fs::File;
     use ::std::io::Read;
     use ::std::io::Write;
     use ::std::path::Path;
     use ::std::path::PathBuf;
 
+    #[cfg(feature = "async")]
+    use ::filetime::{set_file_times, FileTime};
     use ::tempfile::tempdir;
 
     use crate::{shred, ShredConfig};
@@ -86,7 +326,7 @@ mod tests {
 
     fn read_file(pth: &Path) -> Vec<u8> {
         let mut data = vec![];
-        File::open(&pth).unwrap().read_to_end(&mut data).unwrap();
+        File::open(pth).unwrap().read_to_end(&mut data).unwrap();
         data
     }
 
@@ -123,6 +363,20 @@ mod tests {
         assert!(!pth2.exists());
     }
 
+    #[test]
+    fn wipe_dirents_after_shredding() {
+        let dir = tempdir().unwrap();
+        let pth1 = make_file(dir.path(), "file_1.txt");
+        let pth2 = make_file(dir.path(), "other_file.bye");
+        let mut config =
+            ShredConfig::non_interactive(vec![&pth1, &pth2], Verbosity::Debug, false, 2, 3);
+        config.wipe_dirents = true;
+        shred(&config).unwrap();
+        assert!(!pth1.exists());
+        assert!(!pth2.exists());
+        assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+    }
+
     #[test]
     fn owned_paths() {
         let dir = tempdir().unwrap();
@@ -142,6 +396,80 @@ mod tests {
         assert!(!pth2.exists());
     }
 
+    #[test]
+    fn sparse_data_only_keeps_holes() {
+        let dir = tempdir().unwrap();
+        let pth = make_file(dir.path(), "sparse.img");
+        File::options()
+            .write(true)
+            .open(&pth)
+            .unwrap()
+            .set_len(4 * 1024 * 1024)
+            .unwrap();
+        let mut config = ShredConfig::non_interactive(
+            vec![&pth],       // files
+            Verbosity::Quiet, // verbosity
+            true,             // keep_files
+            2,                // overwrite_count
+            1,                // rename_count
+        );
+        config.sparse = SparseMode::DataOnly;
+        shred(&config).unwrap();
+        let data = read_file(&pth);
+        assert_eq!(data.len(), 4 * 1024 * 1024);
+        assert!(!data.starts_with(PREFIX));
+        let allocated = crate::inspect::sparse::allocated_size(&pth.metadata().unwrap());
+        assert!(allocated < 1024 * 1024);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn non_utf8_names() {
+        use ::std::ffi::OsStr;
+        use ::std::os::unix::ffi::OsStrExt;
+
+        let dir = tempdir().unwrap();
+        let pth1 = dir.path().join(OsStr::from_bytes(b"latin1_caf\xe9.txt"));
+        let pth2 = dir.path().join(OsStr::from_bytes(b"\xff\xfe"));
+        fs::write(&pth1, PREFIX).unwrap();
+        fs::write(&pth2, PREFIX).unwrap();
+        for strategy in [RenameStrategy::Scrambled, RenameStrategy::Shrinking] {
+            let mut config = ShredConfig::non_interactive(
+                vec![&pth1, &pth2], // files
+                Verbosity::Debug,   // verbosity
+                false,              // keep_files
+                2,                  // overwrite_count
+                3,                  // rename_count
+            );
+            config.rename_strategy = strategy;
+            shred(&config).unwrap();
+            assert!(!pth1.exists());
+            assert!(!pth2.exists());
+            assert_eq!(dir.path().read_dir().unwrap().count(), 0);
+            fs::write(&pth1, PREFIX).unwrap();
+            fs::write(&pth2, PREFIX).unwrap();
+        }
+    }
+
+    #[test]
+    fn remove_in_scratch_dir() {
+        let dir = tempdir().unwrap();
+        let pth = make_file(dir.path(), "secret.txt");
+        let scratch = dir.path().join("scratch");
+        let mut config = ShredConfig::non_interactive(
+            vec![&pth],       // files
+            Verbosity::Debug, // verbosity
+            false,            // keep_files
+            1,                // overwrite_count
+            2,                // rename_count
+        );
+        config.scratch_dir = ScratchDir::Dir(scratch.clone());
+        shred(&config).unwrap();
+        assert!(!pth.exists());
+        assert_eq!(scratch.read_dir().unwrap().count(), 0);
+        assert_eq!(dir.path().read_dir().unwrap().count(), 1);
+    }
+
     #[test]
     fn test_shred_file() {
         let dir = tempdir().unwrap();
@@ -149,4 +477,94 @@ mod tests {
         shred_file(&pth1).unwrap();
         assert!(!pth1.exists());
     }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn shred_async_all_files() {
+        let dir = tempdir().unwrap();
+        let pth1 = make_file(dir.path(), "first.txt");
+        let pth2 = make_file(dir.path(), "second.txt");
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let config = ShredConfig::interactive(
+            vec![&pth1, &pth2], // files
+            true,               // confirmation_prompt
+            Verbosity::Quiet,   // verbosity
+            false,              // keep_files
+            2,                  // overwrite_count
+            2,                  // rename_count
+        );
+        let res = runtime.block_on(shred_async(&config, None));
+        assert!(res.unwrap_err().contains("confirmation"));
+        let config = ShredConfig::non_interactive(
+            vec![&pth1, &pth2], // files
+            Verbosity::Quiet,   // verbosity
+            false,              // keep_files
+            2,                  // overwrite_count
+            2,                  // rename_count
+        );
+        let reports = runtime.block_on(shred_async(&config, None)).unwrap();
+        assert_eq!(reports.len(), 2);
+        assert!(!pth1.exists());
+        assert!(!pth2.exists());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn shred_async_continues_after_failure() {
+        let dir = tempdir().unwrap();
+        let pth1 = make_file(dir.path(), "first.txt");
+        let pth2 = make_file(dir.path(), "second.txt");
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        // The second time, the first file is already gone.
+        let config = ShredConfig::non_interactive(
+            vec![&pth1, &pth1, &pth2], // files
+            Verbosity::Quiet,          // verbosity
+            false,                     // keep_files
+            1,                         // overwrite_count
+            1,                         // rename_count
+        );
+        let res = runtime.block_on(shred_async(&config, None));
+        assert!(res.unwrap_err().contains("first.txt"));
+        assert!(!pth2.exists());
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn dropped_shred_async_restores_dir_times() {
+        let dir = tempdir().unwrap();
+        let pth1 = make_file(dir.path(), "first.txt");
+        let pth2 = make_file(dir.path(), "second.txt");
+        let old = FileTime::from_unix_time(1_000_000_000, 0);
+        set_file_times(dir.path(), old, old).unwrap();
+        let config = ShredConfig::<PathBuf>::builder()
+            .files(vec![pth1.clone(), pth2])
+            .verbosity(Verbosity::Quiet)
+            .overwrite_count(1)
+            .restore_dir_times(true)
+            .build()
+            .unwrap();
+        let (sender, mut receiver) = ::tokio::sync::mpsc::unbounded_channel();
+        let runtime = ::tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let task = runtime.spawn(async move { shred_async(&config, Some(sender)).await });
+        runtime.block_on(async {
+            while let Some(event) = receiver.recv().await {
+                if matches!(event, ShredEvent::Shredded(_)) {
+                    break;
+                }
+            }
+            task.abort();
+            assert!(task.await.unwrap_err().is_cancelled());
+        });
+        // Waits for the phase that was still running in the background.
+        drop(runtime);
+        assert!(!pth1.exists());
+        let restored = fs::metadata(dir.path()).unwrap();
+        assert_eq!(FileTime::from_last_modification_time(&restored), old);
+    }
 }
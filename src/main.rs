@@ -2,55 +2,278 @@ use ::std::fmt;
 use ::std::path::PathBuf;
 use ::std::process::exit;
 
+use ::clap::Args;
 use ::clap::Parser;
+use ::clap::Subcommand;
 
+use ::file_shred::escape_path;
 use ::file_shred::shred;
+use ::file_shred::shred_device;
+use ::file_shred::shred_directory_entries;
+use ::file_shred::shred_free_space;
+use ::file_shred::shred_truncate_with;
+use ::file_shred::DeviceConfig;
+use ::file_shred::FreeSpaceConfig;
+use ::file_shred::RenameStrategy;
+use ::file_shred::Scheme;
+use ::file_shred::ScratchDir;
 use ::file_shred::ShredConfig;
 use ::file_shred::ShredResult;
+use ::file_shred::SparseMode;
+use ::file_shred::TimestampPolicy;
+use ::file_shred::TruncateConfig;
 use ::file_shred::Verbosity;
 
+use crate::settings::Settings;
+
+mod settings;
+
 #[derive(Debug, Parser)]
 #[clap(
     name = "Shred",
     author = "github.com/mverleg/file_shred",
-    about = "Securely erase one or more files."
+    about = "Securely erase one or more files.",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 pub struct ShredArguments {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     #[clap(name = "FILES", required = true)]
-    /// One or more paths to input files (absolute or relative)
+    /// One or more paths to input files (absolute or relative); put `--` before files named like a subcommand
     files: Vec<PathBuf>,
 
     #[clap(short = 'y', long)]
     /// Delete files without asking for confirmation.
     no_confirm: bool,
 
-    #[clap(short = 'v', long)]
+    #[clap(short = 'v', long, global = true)]
     /// Show debug information, especially on errors.
     debug: bool,
 
-    #[clap(conflicts_with = "debug", short = 'q', long)]
+    #[clap(conflicts_with = "debug", short = 'q', long, global = true)]
     /// Do not show progress or other non-critical output.
     quiet: bool,
 
+    #[clap(long, global = true, value_name = "FILE")]
+    /// Config file with default options and profiles [default: $XDG_CONFIG_HOME/file_shred/config.toml].
+    config: Option<PathBuf>,
+
+    #[clap(long, global = true, value_name = "NAME")]
+    /// Use the options of this profile from the config file.
+    profile: Option<String>,
+
     #[clap(short = 'k', long)]
     /// Destroy the data, but do not rename or delete the file. Useful for non-regular files like special system devices.
     keep: bool,
 
-    #[clap(long, default_value = "10")]
-    /// Number of times the file is overwritten (at least 1).
-    overwrite_count: u32,
+    #[clap(short = 'f', long)]
+    /// Shred protected files: temporarily make your read-only files and directories writable, and clear immutable and append-only flags (needs root).
+    force: bool,
+
+    #[clap(long)]
+    /// Number of times the file is overwritten (at least 1) [default: 10].
+    overwrite_count: Option<u32>,
 
     #[clap(conflicts_with = "keep", long)]
     /// Number of times the file is renamed.
     rename_count: Option<u32>,
+
+    #[clap(conflicts_with = "keep", long)]
+    /// How to rename: 'scrambled' uses unrelated names, 'shrinking' uses shorter and shorter names like GNU shred (ignores rename-count) [default: scrambled].
+    rename_strategy: Option<RenameStrategy>,
+
+    #[clap(long)]
+    /// How to choose passes: 'fixed' uses overwrite-count, 'auto' picks per file based on the storage type [default: fixed].
+    scheme: Option<Scheme>,
+
+    #[clap(long)]
+    /// Deallocate the file blocks after overwriting, so SSDs and thin-provisioned storage learn they are free.
+    discard: bool,
+
+    #[clap(long, overrides_with = "discard")]
+    /// Do not deallocate the file blocks, e.g. when --discard is set in the config file.
+    no_discard: bool,
+
+    #[clap(long)]
+    /// Afterwards, discard unused blocks of the filesystems containing the files (like fstrim, usually needs root).
+    trim: bool,
+
+    #[clap(long, overrides_with = "trim")]
+    /// Do not trim the filesystems, e.g. when --trim is set in the config file.
+    no_trim: bool,
+
+    #[clap(long)]
+    /// For sparse files: 'data-only' overwrites only allocated regions, 'densify' writes the whole length including holes [default: densify].
+    sparse: Option<SparseMode>,
+
+    #[clap(long)]
+    /// Access and modification times to leave behind: 'zero' (1970), 'random' (within the last year), 'sibling' (copied from another file in the directory) or 'untouched' [default: zero].
+    timestamps: Option<TimestampPolicy>,
+
+    #[clap(conflicts_with = "keep", long)]
+    /// Afterwards, overwrite unused directory entries that may still contain the removed file names.
+    wipe_dirents: bool,
+
+    #[clap(conflicts_with = "keep", long, overrides_with = "wipe_dirents")]
+    /// Do not overwrite unused directory entries, e.g. when --wipe-dirents is set in the config file.
+    no_wipe_dirents: bool,
+
+    #[clap(conflicts_with = "keep", long)]
+    /// Persist the directory after every rename, instead of once after renaming (slower).
+    sync_each_rename: bool,
+
+    #[clap(conflicts_with = "keep", long, overrides_with = "sync_each_rename")]
+    /// Persist the directory once after renaming, e.g. when --sync-each-rename is set in the config file.
+    no_sync_each_rename: bool,
+
+    #[clap(conflicts_with = "keep", long, num_args = 0..=1, require_equals = true, value_name = "DIR")]
+    /// Move files to this directory on the same filesystem before the last renames and removal, or to '.shred-tmp' at the filesystem root (kept for later runs) if no directory is given.
+    scratch_dir: Option<Option<PathBuf>>,
+
+    #[clap(conflicts_with = "keep", long, overrides_with = "scratch_dir")]
+    /// Rename and remove files in their own directory, e.g. when scratch-dir is set in the config file.
+    no_scratch_dir: bool,
+
+    #[clap(conflicts_with = "keep", long)]
+    /// Afterwards, restore the access and modification times of the directories containing the files (change time cannot be restored).
+    restore_dir_times: bool,
+
+    #[clap(conflicts_with = "keep", long, overrides_with = "restore_dir_times")]
+    /// Do not restore directory times, e.g. when --restore-dir-times is set in the config file.
+    no_restore_dir_times: bool,
+
+    #[clap(conflicts_with = "keep", long)]
+    /// Set the secure deletion flag before removing, for filesystems that honor it (most, including ext4, ignore it).
+    secure_deletion_flag: bool,
+
+    #[clap(conflicts_with = "keep", long, overrides_with = "secure_deletion_flag")]
+    /// Do not set the secure deletion flag, e.g. when --secure-deletion-flag is set in the config file.
+    no_secure_deletion_flag: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Overwrite a whole block device, and optionally let the kernel sanitize it. For decommissioning drives.
+    Device(DeviceArguments),
+    /// Overwrite the free space of the filesystem containing a directory, to destroy files deleted earlier.
+    FreeSpace(FreeSpaceArguments),
+    /// Overwrite unused directory entries, which may still contain names of removed files.
+    Dirents(DirentsArguments),
+    /// Shrink a file, overwriting the part that is cut off first (e.g. when compacting a log).
+    Truncate(TruncateArguments),
+    /// Inspect the configuration from the config file, environment and defaults.
+    Config(ConfigArguments),
+}
+
+#[derive(Debug, Args)]
+pub struct DeviceArguments {
+    #[clap(name = "DEVICE")]
+    /// Path of the block device, e.g. /dev/sdb or /dev/loop0.
+    device: PathBuf,
+
+    #[clap(long, default_value = "1")]
+    /// Number of times the device is overwritten (can be 0 when using --sanitize).
+    overwrite_count: u32,
+
+    #[clap(long)]
+    /// After overwriting, erase using BLKSECDISCARD, falling back to BLKDISCARD and then BLKZEROOUT.
+    sanitize: bool,
+
+    #[clap(long, default_value = "0")]
+    /// Byte offset at which to start erasing (multiple of 512).
+    offset: u64,
+
+    #[clap(long)]
+    /// Number of bytes to erase (multiple of 512). Defaults to the rest of the device.
+    length: Option<u64>,
+
+    #[clap(long)]
+    /// Start without typing the device path to confirm.
+    yes_destroy_device: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct FreeSpaceArguments {
+    #[clap(name = "DIR")]
+    /// Directory on the filesystem to wipe; temporary filler files are created here.
+    dir: PathBuf,
+
+    #[clap(short = 'y', long)]
+    /// Start without asking for confirmation.
+    no_confirm: bool,
+
+    #[clap(long, default_value = "1")]
+    /// Number of times the free space is overwritten (at least 1).
+    overwrite_count: u32,
+
+    #[clap(long, default_value = "100")]
+    /// Megabytes to leave free, so that other programs keep working.
+    reserve_mb: u64,
+
+    #[clap(long)]
+    /// Maximum number of megabytes to write, as a safety limit.
+    limit_mb: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct DirentsArguments {
+    #[clap(name = "DIR")]
+    /// Directory whose unused entries to overwrite.
+    dir: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct TruncateArguments {
+    #[clap(name = "FILE")]
+    /// File to shrink.
+    file: PathBuf,
+
+    #[clap(long)]
+    /// Number of bytes to keep; everything after is overwritten and cut off.
+    length: u64,
+
+    #[clap(short = 'y', long)]
+    /// Start without asking for confirmation.
+    no_confirm: bool,
+
+    #[clap(long, default_value = "10")]
+    /// Number of times the cut-off part is overwritten (at least 1).
+    overwrite_count: u32,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigArguments {
+    #[clap(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigAction {
+    /// Print the effective value of each option, and where it came from.
+    Show,
+}
+
+/// Shown for options that were not given on the command line.
+const NOT_GIVEN: &str = "from config file or default";
+
+fn given<T: fmt::Display>(value: &Option<T>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => NOT_GIVEN.to_owned(),
+    }
 }
 
 impl fmt::Display for ShredArguments {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if let Some(command) = &self.command {
+            writeln!(f, "  command: {:?}", command)?;
+        }
         f.write_str("  files:\n")?;
         for file in &self.files {
             f.write_str("  - ")?;
-            f.write_str(file.to_string_lossy().as_ref())?;
+            f.write_str(&escape_path(file))?;
             f.write_str("\n")?;
         }
 
@@ -79,11 +302,31 @@ impl fmt::Display for ShredArguments {
         f.write_str(if self.keep { "keep" } else { "delete" })?;
         f.write_str("\n")?;
 
-        writeln!(f, "overwrite: {} times", self.overwrite_count)?;
+        if let Some(config) = &self.config {
+            writeln!(f, "config file: {}", escape_path(config))?;
+        }
+        if let Some(profile) = &self.profile {
+            writeln!(f, "profile: {}", profile)?;
+        }
+        match self.overwrite_count {
+            Some(overwrite_count) => writeln!(f, "overwrite: {} times", overwrite_count)?,
+            None => writeln!(f, "overwrite: {}", NOT_GIVEN)?,
+        };
         match self.rename_count {
             Some(rename_count) => writeln!(f, "rename: {} times\n", rename_count)?,
             None => write!(f, "rename: not applicable")?,
         };
+        writeln!(f, "rename strategy: {}", given(&self.rename_strategy))?;
+        writeln!(f, "scheme: {}", given(&self.scheme))?;
+        writeln!(f, "discard: {}, trim: {}", self.discard, self.trim)?;
+        writeln!(f, "sparse: {}", given(&self.sparse))?;
+        writeln!(f, "timestamps: {}", given(&self.timestamps))?;
+        writeln!(f, "force: {}", self.force)?;
+        writeln!(f, "wipe directory entries: {}", self.wipe_dirents)?;
+        writeln!(f, "sync after each rename: {}", self.sync_each_rename)?;
+        writeln!(f, "scratch directory: {:?}", self.scratch_dir)?;
+        writeln!(f, "restore directory times: {}", self.restore_dir_times)?;
+        writeln!(f, "secure deletion flag: {}", self.secure_deletion_flag)?;
 
         Ok(())
     }
@@ -97,35 +340,173 @@ pub fn main() {
 }
 
 impl ShredArguments {
-    fn convert(self) -> ShredResult<ShredConfig<PathBuf>> {
-        let verbosity = match (self.debug, self.quiet) {
+    fn verbosity(&self) -> ShredResult<Verbosity> {
+        Ok(match (self.debug, self.quiet) {
             (true, true) => return Err("cannot use quiet mode and debug mode together".to_owned()),
             (true, false) => Verbosity::Debug,
             (false, true) => Verbosity::Quiet,
             (false, false) => Verbosity::Normal,
-        };
-        if self.overwrite_count == 0 {
-            return Err("overwrite-count is 0, but must be at least 1".to_owned());
+        })
+    }
+
+    /// Options from defaults, config file, profile and environment.
+    fn load_settings(&self) -> ShredResult<Settings> {
+        Settings::load(self.config.as_deref(), self.profile.as_deref())
+    }
+
+    /// Override the loaded options by those given on the command line.
+    fn settings(&self, mut settings: Settings) -> ShredResult<Settings> {
+        if let Some(count) = self.overwrite_count {
+            settings.set_cli("overwrite-count", count)?;
+        }
+        if let Some(count) = self.rename_count {
+            settings.set_cli("rename-count", count)?;
+        }
+        if let Some(strategy) = self.rename_strategy {
+            settings.set_cli("rename-strategy", strategy)?;
+        }
+        if let Some(scheme) = self.scheme {
+            settings.set_cli("scheme", scheme)?;
+        }
+        if let Some(sparse) = self.sparse {
+            settings.set_cli("sparse", sparse)?;
+        }
+        if let Some(timestamps) = self.timestamps {
+            settings.set_cli("timestamps", timestamps)?;
+        }
+        match &self.scratch_dir {
+            Some(None) => settings.set_cli("scratch-dir", ScratchDir::FilesystemRoot)?,
+            Some(Some(dir)) => settings.set_cli("scratch-dir", ScratchDir::Dir(dir.clone()))?,
+            None if self.no_scratch_dir => settings.set_cli("scratch-dir", ScratchDir::InPlace)?,
+            None => {}
         }
+        // Flags only override when given, either as `--flag` or as `--no-flag`.
+        let flags = [
+            ("discard", self.discard, self.no_discard),
+            ("trim", self.trim, self.no_trim),
+            ("wipe-dirents", self.wipe_dirents, self.no_wipe_dirents),
+            (
+                "sync-each-rename",
+                self.sync_each_rename,
+                self.no_sync_each_rename,
+            ),
+            (
+                "restore-dir-times",
+                self.restore_dir_times,
+                self.no_restore_dir_times,
+            ),
+            (
+                "secure-deletion-flag",
+                self.secure_deletion_flag,
+                self.no_secure_deletion_flag,
+            ),
+        ];
+        for (name, flag, no_flag) in flags {
+            if flag {
+                settings.set_cli(name, true)?;
+            } else if no_flag {
+                settings.set_cli(name, false)?;
+            }
+        }
+        Ok(settings)
+    }
+
+    fn convert(self, loaded: Settings) -> ShredResult<ShredConfig<PathBuf>> {
+        let verbosity = self.verbosity()?;
+        let settings = self.settings(loaded)?;
         let confirmation_prompt = !self.no_confirm;
-        Ok(ShredConfig::interactive(
+        let mut config = ShredConfig::interactive(
             self.files,
             confirmation_prompt,
             verbosity,
             self.keep,
+            10,
+            10,
+        );
+        settings.apply(&mut config)?;
+        config.force = self.force;
+        // Take the directory as given, since the settings only hold text.
+        if let Some(Some(dir)) = self.scratch_dir {
+            config.scratch_dir = ScratchDir::Dir(dir);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl DeviceArguments {
+    fn convert(self, verbosity: Verbosity) -> ShredResult<DeviceConfig> {
+        let mut config =
+            DeviceConfig::interactive(self.device, verbosity, self.overwrite_count, self.sanitize);
+        config.offset = self.offset;
+        config.length = self.length;
+        config.confirmation_prompt = !self.yes_destroy_device;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl FreeSpaceArguments {
+    fn convert(self, verbosity: Verbosity) -> ShredResult<FreeSpaceConfig> {
+        let mut config = FreeSpaceConfig::interactive(
+            self.dir,
+            !self.no_confirm,
+            verbosity,
             self.overwrite_count,
-            self.rename_count.unwrap_or(10),
-        ))
+        );
+        config.reserve = self.reserve_mb * 1024 * 1024;
+        config.limit = self.limit_mb.map(|mb| mb * 1024 * 1024);
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl TruncateArguments {
+    fn convert(self, verbosity: Verbosity) -> ShredResult<TruncateConfig> {
+        let config = TruncateConfig::interactive(
+            self.file,
+            self.length,
+            !self.no_confirm,
+            verbosity,
+            self.overwrite_count,
+        );
+        config.validate()?;
+        Ok(config)
     }
 }
 
 fn go_shred() -> ShredResult<()> {
-    let args = ShredArguments::parse();
+    let mut args = ShredArguments::parse();
     if args.debug {
         println!("arguments provided:\n{}", args);
     }
-    let config = args.convert()?;
-    shred(&config)
+    match args.command.take() {
+        Some(Command::Device(device_args)) => {
+            let config = device_args.convert(args.verbosity()?)?;
+            shred_device(&config).map(|_| ())
+        }
+        Some(Command::FreeSpace(free_space_args)) => {
+            let config = free_space_args.convert(args.verbosity()?)?;
+            shred_free_space(&config).map(|_| ())
+        }
+        Some(Command::Dirents(dirents_args)) => {
+            shred_directory_entries(&dirents_args.dir, args.verbosity()?).map(|_| ())
+        }
+        Some(Command::Truncate(truncate_args)) => {
+            let config = truncate_args.convert(args.verbosity()?)?;
+            shred_truncate_with(&config).map(|_| ())
+        }
+        Some(Command::Config(config_args)) => match config_args.action {
+            ConfigAction::Show => {
+                print!("{}", args.settings(args.load_settings()?)?);
+                Ok(())
+            }
+        },
+        None => {
+            let loaded = args.load_settings()?;
+            shred(&args.convert(loaded)?)
+        }
+    }
 }
 
 #[cfg(test)]
@@ -134,21 +515,41 @@ mod tests {
 
     use super::*;
 
+    /// Convert with settings from the config file given with `--config` only, ignoring the
+    /// environment, so that the tests do not depend on the user running them.
+    fn convert(args: ShredArguments) -> ShredConfig<PathBuf> {
+        let loaded =
+            Settings::load_with(args.config.as_deref(), args.profile.as_deref(), |_| None).unwrap();
+        args.convert(loaded).unwrap()
+    }
+
     #[test]
     fn parse_args_minimal() {
-        let args = ShredArguments::parse_from(&["shred", "file.txt"]);
-        let config = args.convert().unwrap();
+        let args = ShredArguments::parse_from(["shred", "file.txt"]);
+        let config = convert(args);
         assert!(config.files.contains(&PathBuf::from("file.txt")));
         assert_eq!(1, config.files.len());
         assert_eq!(config.verbosity, Verbosity::Normal);
         assert!(!config.keep_files);
         assert_eq!(config.overwrite_count, 10);
         assert_eq!(config.rename_count, 10);
+        assert_eq!(config.scheme, Scheme::Fixed);
+        assert!(!config.punch_holes);
+        assert!(!config.trim_filesystem);
+        assert_eq!(config.sparse, SparseMode::Densify);
+        assert_eq!(config.timestamps, TimestampPolicy::Zero);
+        assert!(!config.force);
+        assert!(!config.wipe_dirents);
+        assert_eq!(config.rename_strategy, RenameStrategy::Scrambled);
+        assert!(!config.sync_each_rename);
+        assert!(!config.secure_deletion_flag);
+        assert_eq!(config.scratch_dir, ScratchDir::InPlace);
+        assert!(!config.restore_dir_times);
     }
 
     #[test]
     fn parse_args_long() {
-        let args = ShredArguments::parse_from(&[
+        let args = ShredArguments::parse_from([
             "shred",
             "file.txt",
             "-q",
@@ -157,8 +558,15 @@ mod tests {
             "there_are_three_files",
             "--overwrite-count",
             "7",
+            "--scheme",
+            "auto",
+            "--discard",
+            "--sparse",
+            "data-only",
+            "--timestamps",
+            "random",
         ]);
-        let config = args.convert().unwrap();
+        let config = convert(args);
         //TODO @mark: why so many &
         assert!(config.files.contains(&PathBuf::from("file.txt")));
         assert!(config.files.contains(&PathBuf::from("another_file.txt")));
@@ -170,5 +578,151 @@ mod tests {
         assert!(config.keep_files);
         assert_eq!(config.overwrite_count, 7);
         assert_eq!(config.rename_count, 10);
+        assert_eq!(config.scheme, Scheme::Auto);
+        assert!(config.punch_holes);
+        assert_eq!(config.sparse, SparseMode::DataOnly);
+        assert_eq!(config.timestamps, TimestampPolicy::Random);
+    }
+
+    #[test]
+    fn parse_args_scratch_dir() {
+        let args = ShredArguments::parse_from(["shred", "--scratch-dir", "file.txt"]);
+        let config = convert(args);
+        assert_eq!(config.scratch_dir, ScratchDir::FilesystemRoot);
+        assert_eq!(config.files, vec![PathBuf::from("file.txt")]);
+        let args = ShredArguments::parse_from(["shred", "--scratch-dir=/tmp/scratch", "file.txt"]);
+        let config = convert(args);
+        assert_eq!(
+            config.scratch_dir,
+            ScratchDir::Dir(PathBuf::from("/tmp/scratch"))
+        );
+    }
+
+    #[test]
+    fn parse_args_profile() {
+        let dir = ::tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        ::std::fs::write(
+            &path,
+            "rename-count = 3\nscratch-dir = \"filesystem-root\"\n[profile.fast]\noverwrite-count = 1\nscheme = \"auto\"\ndiscard = true\n",
+        )
+        .unwrap();
+        let config_arg = format!("--config={}", path.to_str().unwrap());
+        let args = ShredArguments::parse_from([
+            "shred",
+            &config_arg,
+            "--profile",
+            "fast",
+            "--scheme",
+            "fixed",
+            "file.txt",
+        ]);
+        let config = convert(args);
+        assert_eq!(config.overwrite_count, 1);
+        assert_eq!(config.rename_count, 3);
+        assert_eq!(config.scheme, Scheme::Fixed);
+        assert_eq!(config.scratch_dir, ScratchDir::FilesystemRoot);
+        let args = ShredArguments::parse_from([
+            "shred",
+            &config_arg,
+            "--profile",
+            "fast",
+            "--no-scratch-dir",
+            "--no-discard",
+            "--no-restore-dir-times",
+            "--restore-dir-times",
+            "file.txt",
+        ]);
+        let config = convert(args);
+        assert!(!config.punch_holes);
+        assert!(config.restore_dir_times);
+        assert_eq!(config.scratch_dir, ScratchDir::InPlace);
+        let args = ShredArguments::parse_from(["shred", "config", "show", "--profile", "slow"]);
+        assert!(matches!(args.command, Some(Command::Config(_))));
+        assert_eq!(args.profile.as_deref(), Some("slow"));
+    }
+
+    #[test]
+    fn parse_args_device() {
+        let args = ShredArguments::parse_from([
+            "shred",
+            "device",
+            "/dev/loop0",
+            "--sanitize",
+            "--offset",
+            "1024",
+            "-q",
+        ]);
+        let verbosity = args.verbosity().unwrap();
+        let Some(Command::Device(device_args)) = args.command else {
+            panic!("expected device command");
+        };
+        let config = device_args.convert(verbosity).unwrap();
+        assert_eq!(config.device, PathBuf::from("/dev/loop0"));
+        assert_eq!(config.verbosity, Verbosity::Quiet);
+        assert!(config.confirmation_prompt);
+        assert!(config.sanitize);
+        assert_eq!(config.overwrite_count, 1);
+        assert_eq!(config.offset, 1024);
+        assert_eq!(config.length, None);
+    }
+
+    #[test]
+    fn parse_args_device_yes_destroy() {
+        let args =
+            ShredArguments::parse_from(["shred", "device", "/dev/loop0", "--yes-destroy-device"]);
+        let verbosity = args.verbosity().unwrap();
+        let Some(Command::Device(device_args)) = args.command else {
+            panic!("expected device command");
+        };
+        let config = device_args.convert(verbosity).unwrap();
+        assert!(!config.confirmation_prompt);
+    }
+
+    #[test]
+    fn parse_args_free_space() {
+        let args = ShredArguments::parse_from([
+            "shred",
+            "free-space",
+            "/mnt/data",
+            "-y",
+            "--limit-mb",
+            "50",
+        ]);
+        let verbosity = args.verbosity().unwrap();
+        let Some(Command::FreeSpace(free_space_args)) = args.command else {
+            panic!("expected free-space command");
+        };
+        let config = free_space_args.convert(verbosity).unwrap();
+        assert_eq!(config.dir, PathBuf::from("/mnt/data"));
+        assert!(!config.confirmation_prompt);
+        assert_eq!(config.overwrite_count, 1);
+        assert_eq!(config.reserve, 100 * 1024 * 1024);
+        assert_eq!(config.limit, Some(50 * 1024 * 1024));
+    }
+
+    #[test]
+    fn parse_args_truncate() {
+        let args =
+            ShredArguments::parse_from(["shred", "truncate", "app.log", "--length", "4096", "-y"]);
+        let verbosity = args.verbosity().unwrap();
+        let Some(Command::Truncate(truncate_args)) = args.command else {
+            panic!("expected truncate command");
+        };
+        let config = truncate_args.convert(verbosity).unwrap();
+        assert_eq!(config.file, PathBuf::from("app.log"));
+        assert_eq!(config.length, 4096);
+        assert!(!config.confirmation_prompt);
+        assert_eq!(config.overwrite_count, 10);
+    }
+
+    #[test]
+    fn parse_args_files_named_like_subcommands() {
+        let args = ShredArguments::parse_from(["shred", "--", "device", "truncate"]);
+        assert!(args.command.is_none());
+        assert_eq!(
+            args.files,
+            vec![PathBuf::from("device"), PathBuf::from("truncate")]
+        );
     }
 }
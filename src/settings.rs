@@ -0,0 +1,398 @@
+use ::std::collections::BTreeMap;
+use ::std::env;
+use ::std::fmt;
+use ::std::fs;
+use ::std::path::Path;
+use ::std::path::PathBuf;
+use ::std::str::FromStr;
+
+use ::toml::Table;
+use ::toml::Value;
+
+use ::file_shred::escape_path;
+use ::file_shred::ShredConfig;
+use ::file_shred::ShredResult;
+
+/// Options that can be set in the config file or environment, with their built-in defaults.
+/// Names are the same as the command line flags. `force` and `keep` are left out on purpose:
+/// clearing protection flags or keeping the files should be asked for on every run.
+const OPTIONS: &[(&str, &str)] = &[
+    ("overwrite-count", "10"),
+    ("rename-count", "10"),
+    ("rename-strategy", "scrambled"),
+    ("scheme", "fixed"),
+    ("discard", "false"),
+    ("trim", "false"),
+    ("sparse", "densify"),
+    ("timestamps", "zero"),
+    ("wipe-dirents", "false"),
+    ("sync-each-rename", "false"),
+    ("restore-dir-times", "false"),
+    ("secure-deletion-flag", "false"),
+    ("scratch-dir", "in-place"),
+];
+
+/// Prefix of environment variables, e.g. `FILE_SHRED_OVERWRITE_COUNT`.
+const ENV_PREFIX: &str = "FILE_SHRED_";
+
+/// Where the value of an option came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SettingSource {
+    Default,
+    File(PathBuf),
+    Profile(String, PathBuf),
+    Env(String),
+    Cli,
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingSource::Default => f.write_str("default"),
+            SettingSource::File(path) => write!(f, "config file '{}'", escape_path(path)),
+            SettingSource::Profile(name, path) => {
+                write!(f, "profile '{}' in '{}'", name, escape_path(path))
+            }
+            SettingSource::Env(name) => write!(f, "environment variable {}", name),
+            SettingSource::Cli => f.write_str("command line"),
+        }
+    }
+}
+
+/// Option values from all sources, where later sources override earlier ones:
+/// defaults, then the config file, then the selected profile, then environment, then command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    values: BTreeMap<&'static str, (String, SettingSource)>,
+}
+
+/// Default location of the config file, `$XDG_CONFIG_HOME/file_shred/config.toml`.
+pub fn default_config_path() -> Option<PathBuf> {
+    let config_home = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_home.join("file_shred").join("config.toml"))
+}
+
+fn option_name(key: &str) -> Option<&'static str> {
+    let key = key.replace('_', "-");
+    OPTIONS
+        .iter()
+        .map(|(name, _)| *name)
+        .find(|name| *name == key)
+}
+
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Integer(nr) => Some(nr.to_string()),
+        Value::Boolean(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+impl Settings {
+    /// Only the built-in defaults.
+    pub fn defaults() -> Self {
+        Settings {
+            values: OPTIONS
+                .iter()
+                .map(|(name, default)| (*name, ((*default).to_owned(), SettingSource::Default)))
+                .collect(),
+        }
+    }
+
+    /// Combine defaults, config file, profile and environment variables. The config file is
+    /// `path` if given (must exist), otherwise the default location (may be missing).
+    pub fn load(path: Option<&Path>, profile: Option<&str>) -> ShredResult<Self> {
+        let path = match path {
+            Some(path) => Some(path.to_owned()),
+            None => default_config_path().filter(|path| path.is_file()),
+        };
+        Settings::load_with(path.as_deref(), profile, |name| env::var(name).ok())
+    }
+
+    /// Like [Settings::load], but only reads the config file if `path` is given, and takes
+    /// environment variables from `lookup`, so that the outcome does not depend on the user.
+    pub fn load_with(
+        path: Option<&Path>,
+        profile: Option<&str>,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> ShredResult<Self> {
+        let mut settings = Settings::defaults();
+        match path {
+            Some(path) => {
+                let text = match fs::read_to_string(path) {
+                    Ok(text) => text,
+                    Err(err) => {
+                        return Err(format!(
+                            "could not read config file '{}': {}",
+                            escape_path(path),
+                            err
+                        ))
+                    }
+                };
+                settings.apply_file(&text, path, profile)?;
+            }
+            None => {
+                if let Some(profile) = profile {
+                    return Err(format!(
+                        "profile '{}' selected, but there is no config file",
+                        profile
+                    ));
+                }
+            }
+        }
+        settings.apply_env(lookup);
+        Ok(settings)
+    }
+
+    fn set_from_table(&mut self, table: &Table, source: &SettingSource) -> ShredResult<()> {
+        for (key, value) in table {
+            let Some(name) = option_name(key) else {
+                return Err(format!("unknown option '{}' in {}", key, source));
+            };
+            let Some(text) = value_text(value) else {
+                return Err(format!(
+                    "option '{}' in {} should be a string, number or boolean",
+                    key, source
+                ));
+            };
+            self.values.insert(name, (text, source.clone()));
+        }
+        Ok(())
+    }
+
+    /// Apply the top-level options of the config file, then those of the selected profile.
+    pub fn apply_file(
+        &mut self,
+        text: &str,
+        path: &Path,
+        profile: Option<&str>,
+    ) -> ShredResult<()> {
+        let mut table = match text.parse::<Table>() {
+            Ok(table) => table,
+            Err(err) => {
+                return Err(format!(
+                    "could not parse config file '{}': {}",
+                    escape_path(path),
+                    err
+                ))
+            }
+        };
+        let profiles = table.remove("profile");
+        self.set_from_table(&table, &SettingSource::File(path.to_owned()))?;
+        let Some(profile) = profile else {
+            return Ok(());
+        };
+        let profile_table = profiles
+            .as_ref()
+            .and_then(|profiles| profiles.get(profile))
+            .and_then(|profile| profile.as_table());
+        let Some(profile_table) = profile_table else {
+            return Err(format!(
+                "profile '{}' not found in config file '{}'",
+                profile,
+                escape_path(path)
+            ));
+        };
+        self.set_from_table(
+            profile_table,
+            &SettingSource::Profile(profile.to_owned(), path.to_owned()),
+        )
+    }
+
+    /// Apply environment variables like `FILE_SHRED_OVERWRITE_COUNT`.
+    pub fn apply_env(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        for (name, _) in OPTIONS {
+            let var = format!("{}{}", ENV_PREFIX, name.replace('-', "_").to_uppercase());
+            if let Some(value) = lookup(&var) {
+                self.values.insert(name, (value, SettingSource::Env(var)));
+            }
+        }
+    }
+
+    /// Set an option from the command line, which overrides all other sources.
+    pub fn set_cli(&mut self, name: &str, value: impl ToString) -> ShredResult<()> {
+        let Some(name) = option_name(name) else {
+            return Err(format!("unknown option '{}'", name));
+        };
+        self.values
+            .insert(name, (value.to_string(), SettingSource::Cli));
+        Ok(())
+    }
+
+    /// The effective value of an option and where it came from.
+    pub fn get(&self, name: &str) -> Option<&(String, SettingSource)> {
+        self.values.get(option_name(name)?)
+    }
+
+    fn parse<T: FromStr>(&self, name: &str) -> ShredResult<T>
+    where
+        T::Err: fmt::Display,
+    {
+        let (text, source) = self
+            .get(name)
+            .ok_or_else(|| format!("unknown option '{}'", name))?;
+        text.parse::<T>().map_err(|err| {
+            format!(
+                "invalid value '{}' for '{}' from {}: {}",
+                text, name, source, err
+            )
+        })
+    }
+
+    /// Set the options of the shred configuration to the effective values.
+    pub fn apply<P: AsRef<Path>>(&self, config: &mut ShredConfig<P>) -> ShredResult<()> {
+        config.overwrite_count = self.parse("overwrite-count")?;
+        config.rename_count = self.parse("rename-count")?;
+        config.rename_strategy = self.parse("rename-strategy")?;
+        config.scheme = self.parse("scheme")?;
+        config.punch_holes = self.parse("discard")?;
+        config.trim_filesystem = self.parse("trim")?;
+        config.sparse = self.parse("sparse")?;
+        config.timestamps = self.parse("timestamps")?;
+        config.wipe_dirents = self.parse("wipe-dirents")?;
+        config.sync_each_rename = self.parse("sync-each-rename")?;
+        config.restore_dir_times = self.parse("restore-dir-times")?;
+        config.secure_deletion_flag = self.parse("secure-deletion-flag")?;
+        config.scratch_dir = self.parse("scratch-dir")?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, _) in OPTIONS {
+            if let Some((value, source)) = self.values.get(name) {
+                writeln!(f, "{} = {}  ({})", name, value, source)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::file_shred::{Scheme, ScratchDir, Verbosity};
+
+    use super::*;
+
+    const CONFIG: &str = r#"
+overwrite-count = 5
+scheme = "auto"
+
+[profile.fast]
+overwrite_count = 1
+discard = true
+
+[profile.compliance]
+overwrite-count = 7
+timestamps = "random"
+"#;
+
+    fn config() -> ShredConfig<PathBuf> {
+        ShredConfig::non_interactive(vec![], Verbosity::Quiet, false, 10, 10)
+    }
+
+    #[test]
+    fn defaults_only() {
+        let mut config = config();
+        config.overwrite_count = 3;
+        Settings::defaults().apply(&mut config).unwrap();
+        assert_eq!(config.overwrite_count, 10);
+        assert_eq!(config.scheme, Scheme::Fixed);
+    }
+
+    #[test]
+    fn file_and_profile() {
+        let path = Path::new("config.toml");
+        let mut settings = Settings::defaults();
+        settings.apply_file(CONFIG, path, Some("fast")).unwrap();
+        let mut config = config();
+        settings.apply(&mut config).unwrap();
+        assert_eq!(config.overwrite_count, 1);
+        assert_eq!(config.scheme, Scheme::Auto);
+        assert!(config.punch_holes);
+        assert_eq!(
+            settings.get("overwrite-count").unwrap().1,
+            SettingSource::Profile("fast".to_owned(), path.to_owned())
+        );
+        assert_eq!(
+            settings.get("scheme").unwrap().1,
+            SettingSource::File(path.to_owned())
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        let mut settings = Settings::defaults();
+        settings
+            .apply_file(CONFIG, Path::new("config.toml"), Some("compliance"))
+            .unwrap();
+        settings.apply_env(|name| match name {
+            "FILE_SHRED_OVERWRITE_COUNT" => Some("8".to_owned()),
+            "FILE_SHRED_RENAME_COUNT" => Some("4".to_owned()),
+            _ => None,
+        });
+        settings.set_cli("overwrite-count", 9).unwrap();
+        let mut config = config();
+        settings.apply(&mut config).unwrap();
+        assert_eq!(config.overwrite_count, 9);
+        assert_eq!(config.rename_count, 4);
+        assert_eq!(
+            settings.get("overwrite-count").unwrap().1,
+            SettingSource::Cli
+        );
+        assert!(settings
+            .to_string()
+            .contains("rename-count = 4  (environment variable FILE_SHRED_RENAME_COUNT)"));
+    }
+
+    #[test]
+    fn scratch_dir_values() {
+        let mut settings = Settings::defaults();
+        let mut config = config();
+        settings.apply(&mut config).unwrap();
+        assert_eq!(config.scratch_dir, ScratchDir::InPlace);
+        settings.apply_env(|name| match name {
+            "FILE_SHRED_SCRATCH_DIR" => Some("/mnt/data/.scratch".to_owned()),
+            _ => None,
+        });
+        settings.apply(&mut config).unwrap();
+        assert_eq!(
+            config.scratch_dir,
+            ScratchDir::Dir(PathBuf::from("/mnt/data/.scratch"))
+        );
+        settings
+            .set_cli("scratch-dir", ScratchDir::FilesystemRoot)
+            .unwrap();
+        settings.apply(&mut config).unwrap();
+        assert_eq!(config.scratch_dir, ScratchDir::FilesystemRoot);
+    }
+
+    #[test]
+    fn unknown_profile() {
+        let mut settings = Settings::defaults();
+        let res = settings.apply_file(CONFIG, Path::new("config.toml"), Some("slow"));
+        assert!(res.unwrap_err().contains("profile 'slow' not found"));
+    }
+
+    #[test]
+    fn unknown_option() {
+        let mut settings = Settings::defaults();
+        let res = settings.apply_file("passes = 3", Path::new("config.toml"), None);
+        assert!(res.unwrap_err().contains("unknown option 'passes'"));
+    }
+
+    #[test]
+    fn invalid_value() {
+        let mut settings = Settings::defaults();
+        settings
+            .apply_file("scheme = \"best\"", Path::new("config.toml"), None)
+            .unwrap();
+        let err = settings.apply(&mut config()).unwrap_err();
+        assert!(err.contains("invalid value 'best' for 'scheme' from config file"));
+    }
+}
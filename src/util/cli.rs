@@ -1,7 +1,10 @@
 use std::io::{stdin, stdout, Write};
+use std::path::Path;
 
 use crate::inspect::collect::FileInfo;
+use crate::inspect::device::DeviceInfo;
 use crate::util::errors::add_err;
+use crate::util::escape::escape_path;
 use crate::ShredResult;
 
 pub fn confirmation_prompt(text: &str, verbose: bool) -> ShredResult<()> {
@@ -33,3 +36,45 @@ pub fn confirm_delete(files: &[FileInfo], verbose: bool) -> ShredResult<()> {
         verbose,
     )
 }
+
+pub fn confirm_device(device: &DeviceInfo, verbose: bool) -> ShredResult<()> {
+    println!("device selected for shredding (use --yes-destroy-device to skip this message)");
+    println!("- {}", device);
+    println!("ALL data in the selected range of this device will be destroyed");
+    let expected = escape_path(&device.path);
+    let mut answer = String::new();
+    print!(
+        "type the device path '{}' to permanently erase it: ",
+        expected
+    );
+    if let Err(err) = stdout().flush() {
+        return Err(add_err("could not show prompt", verbose, err));
+    }
+    if let Err(err) = stdin().read_line(&mut answer) {
+        return Err(add_err("could not get prompt answer", verbose, err));
+    }
+    if answer.trim_end_matches(['\r', '\n']) != expected {
+        return Err("aborting because the typed path did not match the device".to_owned());
+    }
+    Ok(())
+}
+
+pub fn confirm_free_space(dir: &Path, available: u64, verbose: bool) -> ShredResult<()> {
+    println!(
+        "the filesystem containing '{}' has {} bytes ({:.1} GB) available, which will be filled temporarily",
+        escape_path(dir),
+        available,
+        available as f64 / 1e9
+    );
+    confirmation_prompt("overwrite the free space of this filesystem?", verbose)
+}
+
+pub fn confirm_truncate(path: &Path, current: u64, length: u64, verbose: bool) -> ShredResult<()> {
+    println!(
+        "file '{}' is {} bytes; everything after the first {} bytes will be destroyed",
+        escape_path(path),
+        current,
+        length
+    );
+    confirmation_prompt("overwrite and cut off the end of this file?", verbose)
+}
@@ -0,0 +1,54 @@
+use ::std::ffi::OsStr;
+use ::std::fmt::Write;
+use ::std::path::Path;
+
+/// Show a file name without losing information, also if it is not valid UTF-8.
+/// Valid characters are kept, backslashes and control characters are escaped, and
+/// bytes that are not valid UTF-8 are shown as `\xNN`.
+pub fn escape_os(name: &OsStr) -> String {
+    let mut text = String::with_capacity(name.len());
+    for chunk in name.as_encoded_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => text.push_str("\\\\"),
+                '\n' => text.push_str("\\n"),
+                '\r' => text.push_str("\\r"),
+                '\t' => text.push_str("\\t"),
+                c if c.is_control() => write!(text, "\\u{{{:x}}}", c as u32).unwrap(),
+                c => text.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            write!(text, "\\x{:02X}", byte).unwrap();
+        }
+    }
+    text
+}
+
+/// Show a path without losing information, see [escape_os].
+pub fn escape_path(path: &Path) -> String {
+    escape_os(path.as_os_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_plain() {
+        assert_eq!(escape_path(Path::new("/tmp/file é.txt")), "/tmp/file é.txt");
+    }
+
+    #[test]
+    fn escape_special() {
+        assert_eq!(escape_os(OsStr::new("a\\b\nc\u{7}")), "a\\\\b\\nc\\u{7}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn escape_invalid_utf8() {
+        use ::std::os::unix::ffi::OsStrExt;
+        let name = OsStr::from_bytes(b"caf\xe9\xff.txt");
+        assert_eq!(escape_os(name), "caf\\xE9\\xFF.txt");
+    }
+}
@@ -3,3 +3,5 @@ pub use errors::ShredResult;
 pub mod cli;
 #[allow(clippy::module_inception)]
 pub mod errors;
+pub mod escape;
+pub mod privilege;
@@ -0,0 +1,10 @@
+/// Whether the process runs as root, and can therefore change ownership and protected flags.
+#[cfg(unix)]
+pub fn is_root() -> bool {
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+pub fn is_root() -> bool {
+    false
+}